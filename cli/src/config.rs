@@ -0,0 +1,207 @@
+//! The choice of the stores of the processor, given by a TOML file and by the arguments
+
+use clap::ArgEnum;
+use serde::Deserialize;
+use std::{error::Error as StdError, fs};
+
+use engine::{
+    client::{Client, ClientId},
+    transaction::{Transaction, TransactionId},
+};
+use store::{error::Error, store::Store, store_db::StoreDBBuilder, store_mem::StoreMem};
+
+/// The number of transactions kept in memory by a `db` store, the rest of them are on disk
+pub const TX_BUFFER_SIZE: usize = 1_000_000;
+/// The number of clients kept in memory by a `db` store, the rest of them are on disk
+pub const CLIENT_BUFFER_SIZE: usize = 1 << 20;
+
+pub type ClientStore = Box<dyn Store<ClientId, Client> + Send>;
+pub type TransactionStore = Box<dyn Store<TransactionId, Transaction> + Send>;
+pub type RegistryStore = Box<dyn Store<TransactionId, ()> + Send>;
+
+#[derive(ArgEnum, Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    /// Everything is kept in memory
+    Mem,
+    /// The least recently used entries are moved to a temporary database on disk
+    Db,
+}
+
+/// Every setting is optional, the missing ones fall back to the defaults
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub client_store: Option<StoreKind>,
+    pub tx_store: Option<StoreKind>,
+    pub tx_buffer: Option<usize>,
+    /// The directory to create the temporary databases in
+    pub db_path: Option<String>,
+}
+
+impl Config {
+    /// # Errors
+    /// Fs-related errors of reading the file, or it isn't a valid config
+    pub fn read(path: &str) -> Result<Self, Box<dyn StdError>> {
+        Ok(toml::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// The settings of `overrides` take precedence, e.g. the arguments over the file
+    #[must_use]
+    pub fn merge(self, overrides: Self) -> Self {
+        Self {
+            client_store: overrides.client_store.or(self.client_store),
+            tx_store: overrides.tx_store.or(self.tx_store),
+            tx_buffer: overrides.tx_buffer.or(self.tx_buffer),
+            db_path: overrides.db_path.or(self.db_path),
+        }
+    }
+
+    /// The keys that are set to choose the stores, which the state directory keeps in its own
+    /// databases instead, so they can't be combined with it
+    #[must_use]
+    pub fn store_keys(&self) -> Vec<&'static str> {
+        [
+            ("client_store", self.client_store.is_some()),
+            ("tx_store", self.tx_store.is_some()),
+            ("db_path", self.db_path.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(key, set)| set.then_some(key))
+        .collect()
+    }
+
+    /// The number of transactions kept in memory, shared by all the shards
+    #[must_use]
+    pub fn tx_buffer(&self) -> usize {
+        self.tx_buffer.unwrap_or(TX_BUFFER_SIZE)
+    }
+
+    /// In memory by default
+    ///
+    /// # Errors
+    /// Fs-related errors of creating the database
+    pub fn client_store(&self) -> Result<ClientStore, Error> {
+        Ok(match self.client_store.unwrap_or(StoreKind::Mem) {
+            StoreKind::Mem => Box::new(StoreMem::new()),
+            StoreKind::Db => Box::new(self.db(CLIENT_BUFFER_SIZE).build()?),
+        })
+    }
+
+    /// On disk by default, every one of the `shards` gets its part of the buffer
+    ///
+    /// # Errors
+    /// Fs-related errors of creating the database
+    pub fn transaction_store(&self, shards: usize) -> Result<TransactionStore, Error> {
+        Ok(match self.tx_store.unwrap_or(StoreKind::Db) {
+            StoreKind::Mem => Box::new(StoreMem::new()),
+            StoreKind::Db => Box::new(self.db(self.tx_buffer() / shards).build()?),
+        })
+    }
+
+    /// The IDs of the transactions accepted by all the shards, kept the same way as the
+    /// transactions themselves
+    ///
+    /// # Errors
+    /// Fs-related errors of creating the database
+    pub fn registry_store(&self) -> Result<RegistryStore, Error> {
+        Ok(match self.tx_store.unwrap_or(StoreKind::Db) {
+            StoreKind::Mem => Box::new(StoreMem::new()),
+            StoreKind::Db => Box::new(self.db(self.tx_buffer()).build()?),
+        })
+    }
+
+    fn db(&self, buffer_size: usize) -> StoreDBBuilder {
+        let builder = StoreDBBuilder::new(buffer_size);
+        match &self.db_path {
+            Some(dir) => builder.set_temp_dir(dir.clone()),
+            None => builder,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use random_string::generate;
+    use std::{
+        env::temp_dir,
+        fs::{remove_file, write},
+    };
+
+    use super::{Config, StoreKind, TX_BUFFER_SIZE};
+
+    #[test]
+    fn read() {
+        let config = read_toml("client_store = \"db\"\ntx_buffer = 10\ndb_path = \"/tmp\"\n")
+            .expect("Config read");
+        assert_eq!(Some(StoreKind::Db), config.client_store);
+        assert_eq!(None, config.tx_store);
+        assert_eq!(10, config.tx_buffer());
+        assert_eq!(Some("/tmp"), config.db_path.as_deref());
+    }
+
+    #[test]
+    fn read_errors() {
+        for toml in [
+            "client_stores = \"db\"\n",
+            "tx_store = \"disk\"\n",
+            "tx_buffer = -1\n",
+        ] {
+            assert!(read_toml(toml).is_err(), "{toml} rejected");
+        }
+        assert!(Config::read("/nonexistent/config.toml").is_err());
+    }
+
+    #[test]
+    fn merge() {
+        let config = Config {
+            client_store: Some(StoreKind::Db),
+            tx_store: Some(StoreKind::Db),
+            tx_buffer: None,
+            db_path: Some("/tmp".to_string()),
+        }
+        .merge(Config {
+            client_store: None,
+            tx_store: Some(StoreKind::Mem),
+            tx_buffer: Some(10),
+            db_path: None,
+        });
+        assert_eq!(Some(StoreKind::Db), config.client_store);
+        assert_eq!(Some(StoreKind::Mem), config.tx_store);
+        assert_eq!(10, config.tx_buffer());
+        assert_eq!(Some("/tmp"), config.db_path.as_deref());
+        assert_eq!(TX_BUFFER_SIZE, Config::default().tx_buffer());
+    }
+
+    #[test]
+    fn store_keys() {
+        assert!(Config::default().store_keys().is_empty());
+        assert!(Config {
+            tx_buffer: Some(10),
+            ..Config::default()
+        }
+        .store_keys()
+        .is_empty());
+        assert_eq!(
+            vec!["client_store", "db_path"],
+            Config {
+                client_store: Some(StoreKind::Mem),
+                db_path: Some("/tmp".to_string()),
+                ..Config::default()
+            }
+            .store_keys()
+        );
+    }
+
+    fn read_toml(toml: &str) -> Result<Config, Box<dyn std::error::Error>> {
+        let path = format!(
+            "{}/config_{}.toml",
+            temp_dir().display(),
+            generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+        );
+        write(&path, toml).expect("Config written");
+        let config = Config::read(&path);
+        remove_file(path).expect("Temporary file removed");
+        config
+    }
+}
@@ -1,26 +1,214 @@
 //! CLI interface to the [Simple Payment Engine](../engine/index.html)
 //! built on top of the [Store Engine](../store/index.html)
 
-use clap::Parser;
+use clap::{ArgEnum, Parser};
+use std::{
+    cell::Cell,
+    fs::File,
+    io::{self, Read},
+    process::ExitCode,
+    sync::Arc,
+};
 
 use engine::{
-    processor::Processor,
-    write_csv::{write_csv, Output},
+    amount::SCALE,
+    client::{Client, ClientCSV, ClientId},
+    currency::Currency,
+    format::{self, InputFormat, Malformed, OutputFormat, Records, Row},
+    ledger::Ledger,
+    processor::{NegativeBalances, Processor, WithdrawalDisputes},
+    sharded_processor::ShardedProcessor,
+    stats::ProcessingStats,
+    transaction::{Transaction, TransactionId},
+    write_csv::Output,
 };
-use store::{store_db::StoreDBBuilder, store_mem::StoreMem};
+use store::{store::Store, store_db::StoreDBBuilder};
+
+mod config;
+mod rejections;
+mod state;
+
+use config::{Config, StoreKind, CLIENT_BUFFER_SIZE};
+use rejections::Rejections;
+use state::State;
+
+/// The input file name that stands for STDIN, its offset isn't kept by the state
+const STDIN: &str = "-";
+const CHECKPOINT_INTERVAL: usize = 100_000;
+const JOURNAL_BUFFER_SIZE: usize = 1024;
 
 #[derive(Parser)]
 #[clap(name = "Payment Engine")]
 #[clap(author = "Gregory Arefyev <gregory@recom.live>")]
 #[clap(version = "0.1.0")]
 pub struct Args {
-    #[clap(value_parser, help = "Input file")]
-    pub input_file: String,
+    #[clap(
+        value_parser,
+        required = true,
+        help = "Input files processed in order, - for STDIN"
+    )]
+    pub input_files: Vec<String>,
     #[clap(short, long, help = "Increase log level")]
     pub verbose: bool,
+    #[clap(
+        short,
+        long,
+        default_value_t = 1,
+        help = "Number of threads to process the input in, sharded by client, \
+                a transfer between clients of different shards is rejected"
+    )]
+    pub shards: usize,
+    #[clap(
+        long,
+        conflicts_with = "shards",
+        help = "Directory to persist clients, transactions and input offsets in between runs"
+    )]
+    pub state_dir: Option<String>,
+    #[clap(
+        long,
+        help = "Stream the accounts in no particular order instead of sorting them by client"
+    )]
+    pub unsorted: bool,
+    #[clap(
+        long,
+        arg_enum,
+        default_value = "reject",
+        help = "Whether to reject the disputes of withdrawals or to reverse the withdrawals"
+    )]
+    pub withdrawal_disputes: WithdrawalDisputes,
+    #[clap(
+        long,
+        help = "Allow disputes to make the available funds negative, such accounts are flagged \
+                for collections in the output"
+    )]
+    pub negative_balances: bool,
+    #[clap(
+        long,
+        help = "Accept the administrative actions (unlock, close) of the support team in the input"
+    )]
+    pub admin: bool,
+    #[clap(
+        long,
+        conflicts_with = "shards",
+        help = "Directory of the journal to append the accepted records to, for an audit to replay"
+    )]
+    pub journal: Option<String>,
+    #[clap(
+        long,
+        help = "CSV file to report the rejected and the unparsable rows of the input to"
+    )]
+    pub errors_out: Option<String>,
+    #[clap(
+        long,
+        conflicts_with_all = &["shards", "state-dir"],
+        help = "CSV file to write the trial balance of the double-entry ledger to"
+    )]
+    pub ledger: Option<String>,
+    #[clap(
+        long,
+        value_parser,
+        default_value = "USD",
+        help = "Currency of the rows of the input that don't have one"
+    )]
+    pub currency: Currency,
+    #[clap(
+        long,
+        help = "Add the currency column to the accounts, one row per client and currency"
+    )]
+    pub currency_column: bool,
+    #[clap(long, arg_enum, default_value = "csv", help = "Format of the input")]
+    pub input_format: InputFormatArg,
+    #[clap(
+        long,
+        arg_enum,
+        default_value = "csv",
+        help = "Format of the accounts and of the ledger written"
+    )]
+    pub output_format: OutputFormatArg,
+    #[clap(
+        short,
+        long,
+        help = "File to write the accounts to instead of STDOUT, it's replaced once complete"
+    )]
+    pub output: Option<String>,
+    #[clap(long, help = "Omit the header row of the accounts in CSV")]
+    pub no_header: bool,
+    #[clap(
+        long,
+        default_value_t = SCALE,
+        help = "Number of decimal places of the amounts of the accounts"
+    )]
+    pub precision: usize,
+    #[clap(
+        long,
+        help = "TOML file with the settings of the stores, the arguments take precedence"
+    )]
+    pub config: Option<String>,
+    #[clap(
+        long,
+        arg_enum,
+        conflicts_with = "state-dir",
+        help = "Store of the clients, mem by default"
+    )]
+    pub client_store: Option<StoreKind>,
+    #[clap(
+        long,
+        arg_enum,
+        conflicts_with = "state-dir",
+        help = "Store of the transactions, db by default"
+    )]
+    pub tx_store: Option<StoreKind>,
+    #[clap(
+        long,
+        help = "Number of transactions kept in memory by the db store, 1000000 by default"
+    )]
+    pub tx_buffer: Option<usize>,
+    #[clap(
+        long,
+        conflicts_with = "state-dir",
+        help = "Directory to create the temporary databases of the db stores in"
+    )]
+    pub db_path: Option<String>,
+    #[clap(
+        long,
+        help = "JSON file to write the summary of the run to instead of printing it to STDERR"
+    )]
+    pub stats: Option<String>,
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+pub enum InputFormatArg {
+    Csv,
+    Jsonl,
+}
+
+impl From<InputFormatArg> for InputFormat {
+    fn from(arg: InputFormatArg) -> Self {
+        match arg {
+            InputFormatArg::Csv => Self::Csv,
+            InputFormatArg::Jsonl => Self::JsonLines,
+        }
+    }
+}
+
+#[derive(ArgEnum, Clone, Copy)]
+pub enum OutputFormatArg {
+    Csv,
+    Jsonl,
+    Json,
+}
+
+impl From<OutputFormatArg> for OutputFormat {
+    fn from(arg: OutputFormatArg) -> Self {
+        match arg {
+            OutputFormatArg::Csv => Self::Csv,
+            OutputFormatArg::Jsonl => Self::JsonLines,
+            OutputFormatArg::Json => Self::Json,
+        }
+    }
 }
 
-fn main() {
+fn main() -> ExitCode {
     let args = Args::parse();
     let log_level = if args.verbose {
         tracing::Level::WARN
@@ -33,39 +221,301 @@ fn main() {
         .with_max_level(log_level)
         .init();
 
-    let mut processor = Processor::new(
-        // the size of the in-memory part of the StoreDB could be a cli argument
-        // as well as the choice of the store engines for clients and transactions
-        // the below hadcoded configuration is inspired by the description of the problem at hand
-        StoreMem::new(),
-        StoreDBBuilder::new(1_000_000)
-            .build()
-            .expect("StoreDB created"),
+    let config = args
+        .config
+        .as_deref()
+        .map_or_else(|| Ok(Config::default()), Config::read)
+        .expect("Config read")
+        .merge(Config {
+            client_store: args.client_store,
+            tx_store: args.tx_store,
+            tx_buffer: args.tx_buffer,
+            db_path: args.db_path.clone(),
+        });
+    if args.state_dir.is_some() {
+        let keys = config.store_keys();
+        if !keys.is_empty() {
+            eprintln!(
+                "error: The keys {} of the config can't be used with '--state-dir', \
+                 it keeps the stores in its own databases",
+                keys.join(", ")
+            );
+            return ExitCode::from(2);
+        }
+    }
+
+    let mut state = args
+        .state_dir
+        .as_ref()
+        .map(|dir| State::open(dir).expect("State opened"));
+    // the number of the records of every input consumed so far, including the previous runs
+    let consumed = args
+        .input_files
+        .iter()
+        .map(|input| {
+            Cell::new(match &mut state {
+                Some(state) if input != STDIN => state.offset(input).expect("Offset read"),
+                _ => 0,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut readers: Vec<Records> = vec![];
+    for input in &args.input_files {
+        let reader = if let Some(previous) = readers.last() {
+            Records::continued(open(input), args.input_format.into(), previous)
+        } else {
+            Records::from_reader(open(input), args.input_format.into())
+        };
+        readers.push(reader.expect("Input reader created").with_input(input));
+    }
+    let rejections =
+        Arc::new(Rejections::create(args.errors_out.as_deref()).expect("Errors output created"));
+    let records = readers
+        .into_iter()
+        .zip(&consumed)
+        .flat_map(|(reader, consumed)| {
+            let rejections = &rejections;
+            reader
+                .skip(usize::try_from(consumed.get()).expect("Offset fits in memory"))
+                .inspect(move |_| consumed.set(consumed.get() + 1))
+                .filter_map(move |row| parse(rejections, row))
+        });
+
+    if let Some(state) = &mut state {
+        let processor = Processor::new(
+            state
+                .client_store(CLIENT_BUFFER_SIZE)
+                .expect("Client store opened"),
+            state
+                .transaction_store(config.tx_buffer())
+                .expect("Transaction store opened"),
+        );
+        let processor = configure(&args, processor);
+        // the stores of the state are written through, so the offsets are stored right after
+        // every record and a rerun doesn't apply any of the records twice
+        run(&args, processor, records, &rejections, 1, |processor| {
+            processor.flush().expect("Processor flushed");
+            for (input, consumed) in args.input_files.iter().zip(&consumed) {
+                if input != STDIN {
+                    state
+                        .set_offset(input, consumed.get())
+                        .expect("Offset stored");
+                }
+            }
+        })
+    } else if args.shards > 1 {
+        run_sharded(&args, &config, records, Arc::clone(&rejections))
+    } else {
+        run(
+            &args,
+            configure(
+                &args,
+                Processor::new(
+                    config.client_store().expect("Client store created"),
+                    config.transaction_store(1).expect("Transaction store created"),
+                ),
+            ),
+            records,
+            &rejections,
+            CHECKPOINT_INTERVAL,
+            |_| {},
+        )
+    }
+}
+
+/// `checkpoint` is called every `interval` records and once all of them are processed.
+/// The processor is flushed and dropped before returning, so that nothing buffered by its stores
+/// and journal is lost when the process exits with a failure
+fn run<CS, TS>(
+    args: &Args,
+    mut processor: Processor<CS, TS>,
+    records: impl Iterator<Item = Row>,
+    rejections: &Rejections,
+    interval: usize,
+    mut checkpoint: impl FnMut(&mut Processor<CS, TS>),
+) -> ExitCode
+where
+    CS: Store<ClientId, Client>,
+    TS: Store<TransactionId, Transaction>,
+{
+    for (i, row) in records.enumerate() {
+        if let Err(error) = processor.process(&row.record) {
+            rejections.reject(&row.record, &row.source, &error);
+            if error.is_fatal() {
+                // the rows since the last checkpoint are processed again by the next run, which
+                // under `--state-dir` is only the failed row
+                if let Err(error) = processor.flush() {
+                    log::error!("Processor not flushed: {:?}", error);
+                }
+                return ExitCode::FAILURE;
+            }
+        }
+        if (i + 1) % interval == 0 {
+            checkpoint(&mut processor);
+        }
+    }
+    checkpoint(&mut processor);
+    report_stats(args, processor.stats().expect("Stats read"), rejections);
+
+    // the ledger and the accounts are written regardless, so that a mismatch can be looked into
+    let mut reconciled = true;
+    if let (Some(path), Some(ledger)) = (&args.ledger, processor.ledger()) {
+        if let Err(error) = processor.reconcile_ledger() {
+            log::error!("Ledger doesn't reconcile: {:?}", error);
+            reconciled = false;
+        }
+        format::write(
+            &Output::File(path),
+            args.output_format.into(),
+            true,
+            ledger.ledger_csv(),
+        )
+        .expect("Ledger written");
+    }
+
+    write_accounts(
+        args,
+        if args.unsorted {
+            processor.clients_csv_unsorted()
+        } else {
+            processor.clients_csv()
+        }
+        .expect("Clients read"),
     );
+    if reconciled {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn run_sharded(
+    args: &Args,
+    config: &Config,
+    records: impl Iterator<Item = Row>,
+    rejections: Arc<Rejections>,
+) -> ExitCode {
+    let shards = args.shards;
+    let processors = (0..shards)
+        .map(|_| {
+            configure(
+                args,
+                Processor::new(
+                    config.client_store().expect("Client store created"),
+                    config
+                        .transaction_store(shards)
+                        .expect("Transaction store created"),
+                ),
+            )
+        })
+        .collect();
+    let on_error = Arc::clone(&rejections);
+    let registry = config.registry_store().expect("Registry store created");
+    let mut processor =
+        ShardedProcessor::new(processors, registry, move |record, source, error| {
+            on_error.reject(record, source, error);
+        })
+        .expect("Shards started");
 
-    let mut reader = csv::Reader::from_path(&args.input_file).expect("CSV reader created");
-
-    // possible improvement:
-    //  the processing can be parallelized in N threads
-    //  each thread will have its own instance of the `Processor`
-    //  there's no data that the threads would need to share
-    //  as long as the sharding is done based on `record.client_id`
-    //  i.e. `process_thread_id = record.client_id % n_threads`
-    for record in reader.deserialize() {
-        if let Err(error) = record {
-            log::error!("Failed to parse CSV [{}]: {}", args.input_file, error);
-        } else if let Err(error) = processor.process(record.as_ref().unwrap()) {
-            // possible improvement:
-            //  errors that come from the Store engine should have a higher rank
-            //  right now they are mixed together with the errors of the Processor
-            log::warn!("Failed to process record [{:?}]: {}", record, error);
+    for row in records {
+        if processor.process(row.record, row.source).is_err() {
+            // the shard has stopped, the cause is reported by `join` below
+            break;
         }
     }
 
-    write_csv(
-        // the output (stdout or a file) could be an optional cli argument
-        &Output::STDOUT,
-        processor.clients_csv().expect("Clients read"),
+    if let Err(error) = processor.join() {
+        // the shards that haven't failed are dropped, hence flushed, along with `processor`
+        log::error!("Processing stopped: {:?}", error);
+        return ExitCode::FAILURE;
+    }
+    report_stats(args, processor.stats().expect("Stats read"), &rejections);
+
+    write_accounts(
+        args,
+        if args.unsorted {
+            processor.clients_csv_unsorted()
+        } else {
+            processor.clients_csv()
+        }
+        .expect("Clients read"),
+    );
+    ExitCode::SUCCESS
+}
+
+/// Writes the summary of the run to the file given in the arguments or prints it to STDERR
+fn report_stats(args: &Args, mut stats: ProcessingStats, rejections: &Rejections) {
+    rejections.count_malformed(&mut stats);
+    if let Some(path) = &args.stats {
+        format::write(
+            &Output::File(path),
+            OutputFormat::JsonLines,
+            true,
+            std::iter::once(stats),
+        )
+        .expect("Stats written");
+    } else {
+        eprintln!("{stats}");
+    }
+}
+
+/// Writes the accounts to the output given in the arguments
+fn write_accounts(args: &Args, clients: impl Iterator<Item = ClientCSV>) {
+    format::write(
+        &args.output.as_deref().map_or(Output::STDOUT, Output::File),
+        args.output_format.into(),
+        !args.no_header,
+        clients.map(|client| client.with_precision(args.precision)),
     )
     .expect("Written");
 }
+
+/// Applies the policies and the optional features given in the arguments
+fn configure<CS, TS>(args: &Args, processor: Processor<CS, TS>) -> Processor<CS, TS>
+where
+    CS: Store<ClientId, Client>,
+    TS: Store<TransactionId, Transaction>,
+{
+    let processor = processor
+        .set_withdrawal_disputes(args.withdrawal_disputes)
+        .set_negative_balances(if args.negative_balances {
+            NegativeBalances::Allow
+        } else {
+            NegativeBalances::Reject
+        })
+        .set_admin_input(args.admin)
+        .set_default_currency(args.currency.clone())
+        .set_currency_column(args.currency_column);
+    let processor = if args.ledger.is_some() {
+        processor.set_ledger(Ledger::default())
+    } else {
+        processor
+    };
+    if let Some(dir) = &args.journal {
+        processor
+            .set_journal(Box::new(
+                StoreDBBuilder::new(JOURNAL_BUFFER_SIZE)
+                    .set_db_path(dir.clone())
+                    .build()
+                    .expect("Journal opened"),
+            ))
+            .expect("Journal read")
+    } else {
+        processor
+    }
+}
+
+fn open(input: &str) -> Box<dyn Read> {
+    if input == STDIN {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(input).expect("Input file opened"))
+    }
+}
+
+/// The rows that can't be parsed are reported right away and skipped
+fn parse(rejections: &Rejections, row: Result<Row, Malformed>) -> Option<Row> {
+    row.map_err(|malformed| rejections.malformed(&malformed))
+        .ok()
+}
@@ -0,0 +1,139 @@
+//! The report of the rejected and the unparsable rows of the input, one CSV row per each:
+//! the input, the line number, the code of the error and the original fields.
+//!
+//! A header row precedes the rows whenever the headers of the input change, e.g. when the files
+//! of the input have different columns, so that every row is labelled by its own headers
+
+use csv::{StringRecord, Writer, WriterBuilder};
+use std::{
+    fs::File,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use engine::{
+    error::Error,
+    format::{Malformed, Source},
+    input::Record,
+    stats::ProcessingStats,
+};
+
+/// The code of a row that can't be parsed into a record
+const MALFORMED_CODE: &str = "input.malformed";
+
+pub struct Rejections {
+    report: Option<Mutex<Report>>,
+    /// The rows that haven't reached the processor, hence aren't in its stats
+    malformed: AtomicU64,
+}
+
+struct Report {
+    writer: Writer<File>,
+    /// The headers of the last header row written
+    headers: Option<Arc<StringRecord>>,
+}
+
+/// A row of the report
+struct Rejected<'a> {
+    input: Option<&'a str>,
+    line: Option<u64>,
+    code: &'a str,
+    headers: &'a Arc<StringRecord>,
+    fields: Option<&'a StringRecord>,
+}
+
+impl Rejections {
+    /// Only logs the rejections when `path` is `None`
+    ///
+    /// # Errors
+    /// Fs-related errors of creating the file
+    pub fn create(path: Option<&str>) -> Result<Self, csv::Error> {
+        let report = path
+            .map(|path| -> Result<_, csv::Error> {
+                // the rows of the input may have fewer fields than the headers
+                Ok(Mutex::new(Report {
+                    writer: WriterBuilder::new().flexible(true).from_path(path)?,
+                    headers: None,
+                }))
+            })
+            .transpose()?;
+        Ok(Self {
+            report,
+            malformed: AtomicU64::new(0),
+        })
+    }
+
+    pub fn reject(&self, record: &Record, source: &Source, error: &Error) {
+        if error.is_fatal() {
+            log::error!("Failed to process record [{:?}]: {:?}", record, error);
+        } else {
+            log::warn!("Failed to process record [{:?}]: {:?}", record, error);
+        }
+        self.write(&Rejected {
+            input: source.input.as_deref(),
+            line: Some(source.line),
+            code: error.code(),
+            headers: &source.headers,
+            fields: Some(&source.fields),
+        });
+    }
+
+    pub fn malformed(&self, malformed: &Malformed) {
+        log::error!(
+            "Failed to parse input [{}]: {:?}",
+            malformed.input.as_deref().unwrap_or_default(),
+            malformed.error
+        );
+        self.malformed.fetch_add(1, Ordering::Relaxed);
+        self.write(&Rejected {
+            input: malformed.input.as_deref(),
+            line: malformed.line,
+            code: MALFORMED_CODE,
+            headers: &malformed.headers,
+            fields: malformed.fields.as_ref(),
+        });
+    }
+
+    /// Adds the malformed rows to the stats of the processor
+    pub fn count_malformed(&self, stats: &mut ProcessingStats) {
+        stats.add_rejected(MALFORMED_CODE, self.malformed.load(Ordering::Relaxed));
+    }
+
+    /// Every row is flushed right away, so that the report is complete even if the processing
+    /// stops on a fatal error
+    fn write(&self, rejected: &Rejected<'_>) {
+        if let Some(report) = &self.report {
+            let mut report = report.lock().expect("Errors output isn't poisoned");
+            report.write(rejected).expect("Errors output written");
+        }
+    }
+}
+
+impl Report {
+    fn write(&mut self, rejected: &Rejected<'_>) -> Result<(), csv::Error> {
+        if self.headers.as_deref() != Some(rejected.headers.as_ref()) {
+            self.writer.write_record(
+                ["input", "line", "code"]
+                    .into_iter()
+                    .chain(rejected.headers.iter()),
+            )?;
+            self.headers = Some(Arc::clone(rejected.headers));
+        }
+        let line = rejected
+            .line
+            .map(|line| line.to_string())
+            .unwrap_or_default();
+        self.writer.write_record(
+            [
+                rejected.input.unwrap_or_default(),
+                line.as_str(),
+                rejected.code,
+            ]
+            .into_iter()
+            .chain(rejected.fields.into_iter().flatten()),
+        )?;
+        Ok(self.writer.flush()?)
+    }
+}
@@ -0,0 +1,82 @@
+//! The persistent state of the CLI that allows a subsequent run to continue
+//! from the balances left by the previous one.
+//!
+//! All the stores of the state are written through, hence an unclean exit loses nothing
+//! but the offsets that haven't been stored yet, see `set_offset`
+
+use std::{fs::canonicalize, path::PathBuf};
+
+use engine::{
+    client::{Client, ClientId},
+    transaction::{Transaction, TransactionId},
+};
+use store::{
+    error::Error,
+    store::Store,
+    store_db::{Durability, StoreDB, StoreDBBuilder},
+};
+
+const OFFSETS_BUFFER_SIZE: usize = 16;
+
+pub struct State {
+    dir: PathBuf,
+    offsets: StoreDB<String, u64>,
+}
+
+impl State {
+    /// # Errors
+    /// Fs-related errors of opening the offsets database
+    pub fn open(dir: &str) -> Result<Self, Error> {
+        let dir = PathBuf::from(dir);
+        Ok(Self {
+            offsets: StoreDBBuilder::new(OFFSETS_BUFFER_SIZE)
+                .set_db_path(dir.join("offsets").display().to_string())
+                .set_durability(Durability::WriteThrough)
+                .build()?,
+            dir,
+        })
+    }
+
+    /// # Errors
+    pub fn client_store(&self, buffer_size: usize) -> Result<StoreDB<ClientId, Client>, Error> {
+        StoreDBBuilder::new(buffer_size)
+            .set_db_path(self.dir.join("clients").display().to_string())
+            .set_durability(Durability::WriteThrough)
+            .build()
+    }
+
+    /// # Errors
+    pub fn transaction_store(
+        &self,
+        buffer_size: usize,
+    ) -> Result<StoreDB<TransactionId, Transaction>, Error> {
+        StoreDBBuilder::new(buffer_size)
+            .set_db_path(self.dir.join("transactions").display().to_string())
+            .set_durability(Durability::WriteThrough)
+            .build()
+    }
+
+    /// The number of records of the `input` file that have been processed by the previous runs
+    ///
+    /// # Errors
+    pub fn offset(&mut self, input: &str) -> Result<u64, Error> {
+        Ok(self
+            .offsets
+            .get(&offset_key(input))?
+            .copied()
+            .unwrap_or_default())
+    }
+
+    /// Meant to be called right after the record at `offset` is written to the stores, so that
+    /// the offset never falls behind the stores by more than the record being processed
+    ///
+    /// # Errors
+    pub fn set_offset(&mut self, input: &str, offset: u64) -> Result<(), Error> {
+        self.offsets.insert(offset_key(input), offset).map(|_| ())
+    }
+}
+
+/// The same file may be given by different relative paths
+fn offset_key(input: &str) -> String {
+    canonicalize(input).map_or_else(|_| input.to_string(), |path| path.display().to_string())
+}
@@ -0,0 +1,34 @@
+use random_string::generate;
+use std::{
+    env::temp_dir,
+    fs::{remove_file, write},
+    process::Command,
+};
+
+#[test]
+fn store_keys_with_state_dir() {
+    let tmp_file = format!(
+        "{}/config_{}.toml",
+        temp_dir().display(),
+        generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+    );
+    write(&tmp_file, "client_store = \"db\"\ntx_buffer = 10\n").expect("Config written");
+
+    // the state directory isn't even opened
+    let output = Command::new(env!("CARGO_BIN_EXE_pe"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args([
+            "resources/transactions_rejected.csv",
+            "--config",
+            &tmp_file,
+            "--state-dir",
+            "/nonexistent/state",
+        ])
+        .output()
+        .expect("pe run");
+    assert_eq!(Some(2), output.status.code());
+    let stderr = String::from_utf8(output.stderr).expect("UTF-8 error");
+    assert!(stderr.contains("client_store"), "{stderr}");
+
+    remove_file(tmp_file).expect("Temporary file removed");
+}
@@ -0,0 +1,3 @@
+mod config;
+mod rejections;
+mod state;
@@ -0,0 +1,75 @@
+use file_diff::diff;
+use random_string::generate;
+use std::{
+    env::temp_dir,
+    fs::{read_to_string, remove_file},
+    process::Command,
+};
+
+#[test]
+fn errors_out() {
+    let tmp_file = tmp_file("errors");
+    // the second file has another header row, hence it's labelled by another header row
+    run(&[
+        "resources/transactions_rejected.csv",
+        "resources/transactions_rejected_currency.csv",
+        "--errors-out",
+        &tmp_file,
+    ]);
+
+    assert!(diff(&resource("errors_rejected.csv"), &tmp_file));
+
+    remove_file(tmp_file).expect("Temporary file removed");
+}
+
+#[test]
+fn errors_out_sharded() {
+    let tmp_file = tmp_file("errors_sharded");
+    run(&[
+        "resources/transactions_rejected.csv",
+        "--errors-out",
+        &tmp_file,
+        "--shards",
+        "2",
+    ]);
+
+    // the shards report the rejections in no particular order
+    assert_eq!(
+        sorted_lines(&resource("errors_rejected_sharded.csv")),
+        sorted_lines(&tmp_file)
+    );
+
+    remove_file(tmp_file).expect("Temporary file removed");
+}
+
+/// The inputs are given relative to the crate, so that they're named the same in the report
+fn run(args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_pe"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .output()
+        .expect("pe run");
+    assert!(output.status.success(), "{output:?}");
+}
+
+fn sorted_lines(path: &str) -> Vec<String> {
+    let mut lines = read_to_string(path)
+        .expect("File read")
+        .lines()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines
+}
+
+fn tmp_file(name: &str) -> String {
+    format!(
+        "{}/{name}_{}.csv",
+        temp_dir().display(),
+        generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+    )
+}
+
+fn resource(path: &str) -> String {
+    format!("{}/resources/{path}", env!("CARGO_MANIFEST_DIR"))
+}
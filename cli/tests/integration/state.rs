@@ -0,0 +1,54 @@
+use file_diff::diff;
+use random_string::generate;
+use std::{
+    env::temp_dir,
+    fs::{read_to_string, remove_dir_all},
+    process::Command,
+};
+
+#[test]
+fn continued_from_state_dir() {
+    let tmp_dir = format!(
+        "{}/state_{}",
+        temp_dir().display(),
+        generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+    );
+    let state_dir = format!("{tmp_dir}/state");
+    let accounts = format!("{tmp_dir}/accounts.csv");
+    let errors = format!("{tmp_dir}/errors.csv");
+    let run = |input: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_pe"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args([
+                input,
+                "--state-dir",
+                &state_dir,
+                "--output",
+                &accounts,
+                "--errors-out",
+                &errors,
+            ])
+            .output()
+            .expect("pe run");
+        assert!(output.status.success(), "{output:?}");
+    };
+
+    run("resources/transactions_day1.csv");
+    assert!(diff(&resource("accounts_day1.csv"), &accounts));
+
+    // the dispute of the second day refers to a deposit of the first one
+    run("resources/transactions_day2.csv");
+    assert!(diff(&resource("accounts_day2.csv"), &accounts));
+    assert_eq!("", read_to_string(&errors).expect("Errors read"));
+
+    // the rows processed already are skipped, otherwise they'd be rejected as duplicates
+    run("resources/transactions_day2.csv");
+    assert!(diff(&resource("accounts_day2.csv"), &accounts));
+    assert_eq!("", read_to_string(&errors).expect("Errors read"));
+
+    remove_dir_all(tmp_dir).expect("Temporary directory removed");
+}
+
+fn resource(path: &str) -> String {
+    format!("{}/resources/{path}", env!("CARGO_MANIFEST_DIR"))
+}
@@ -0,0 +1,171 @@
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The amount isn't a valid decimal number
+    Malformed,
+    /// The amount has more than four decimal places
+    PrecisionExceeded,
+    /// The amount doesn't fit into the supported range
+    Overflow,
+}
+
+/// The number of decimal places kept by `Amount`
+pub const SCALE: usize = 4;
+
+/// The powers of ten up to `10^SCALE`
+const POWERS: [u64; SCALE + 1] = [1, 10, 100, 1_000, 10_000];
+
+/// An exact fixed-point decimal with four decimal places,
+/// internally a signed number of ten-thousandths
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(i64);
+
+impl Amount {
+    pub const ZERO: Self = Self(0);
+
+    /// Creates an amount out of a number of ten-thousandths, i.e. `from_scaled(15_000)` is `1.5`
+    #[must_use]
+    pub const fn from_scaled(units: i64) -> Self {
+        Self(units)
+    }
+
+    /// The number of ten-thousandths in the amount
+    #[must_use]
+    pub const fn scaled(self) -> i64 {
+        self.0
+    }
+
+    #[must_use]
+    pub const fn is_negative(self) -> bool {
+        self.0 < 0
+    }
+
+    /// Returns `None` on overflow
+    #[must_use]
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        self.0.checked_add(other.0).map(Self)
+    }
+
+    /// Returns `None` on overflow
+    #[must_use]
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.0.checked_sub(other.0).map(Self)
+    }
+
+    /// Rounds half away from zero to `places` decimal places the same way as `Display` does,
+    /// returns `None` on overflow
+    #[must_use]
+    pub fn checked_round(self, places: usize) -> Option<Self> {
+        let divisor = POWERS[SCALE - places.min(SCALE)];
+        let units =
+            i64::try_from((self.0.unsigned_abs() + divisor / 2) / divisor * divisor).ok()?;
+        Some(Self(if self.is_negative() { -units } else { units }))
+    }
+}
+
+impl FromStr for Amount {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input = input.trim();
+        let (negative, unsigned) = if let Some(rest) = input.strip_prefix('-') {
+            (true, rest)
+        } else {
+            (false, input.strip_prefix('+').unwrap_or(input))
+        };
+        let (integer, fraction) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+
+        if (integer.is_empty() && fraction.is_empty())
+            || !integer.bytes().all(|b| b.is_ascii_digit())
+            || !fraction.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(Error::Malformed);
+        }
+        if fraction.len() > SCALE {
+            return Err(Error::PrecisionExceeded);
+        }
+
+        let mut units: i64 = 0;
+        for digit in integer
+            .bytes()
+            .chain(fraction.bytes())
+            .chain(std::iter::repeat_n(b'0', SCALE - fraction.len()))
+        {
+            units = units
+                .checked_mul(10)
+                .and_then(|units| units.checked_add(i64::from(digit - b'0')))
+                .ok_or(Error::Overflow)?;
+        }
+
+        Ok(Self(if negative { -units } else { units }))
+    }
+}
+
+/// Four decimal places unless the precision is given, e.g. `{:.2}`. An amount is rounded half
+/// away from zero to fewer places and padded with zeros to more of them
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precision = f.precision().unwrap_or(SCALE);
+        let places = precision.min(SCALE);
+        let divisor = POWERS[SCALE - places];
+        let units = (self.0.unsigned_abs() + divisor / 2) / divisor;
+        let sign = if self.is_negative() && units > 0 {
+            "-"
+        } else {
+            ""
+        };
+        write!(f, "{sign}{}", units / POWERS[places])?;
+        if precision > 0 {
+            write!(
+                f,
+                ".{:0places$}{:0<padding$}",
+                units % POWERS[places],
+                "",
+                padding = precision - places
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Human-readable formats (CSV) get the decimal string, binary ones (bincode) get the raw integer
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.collect_str(self)
+        } else {
+            serializer.serialize_i64(self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(AmountVisitor)
+        } else {
+            i64::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+struct AmountVisitor;
+
+impl Visitor<'_> for AmountVisitor {
+    type Value = Amount;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a decimal number with at most {SCALE} decimal places")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        value
+            .parse()
+            .map_err(|error| E::custom(format!("invalid amount {value:?}: {error}")))
+    }
+}
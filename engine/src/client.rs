@@ -1,127 +1,454 @@
 use serde::{
-    ser::{Serialize, SerializeStruct, Serializer},
+    ser::{Error as SerError, Serialize, SerializeStruct, Serializer},
     Deserialize, Serialize as SerializeMacro,
 };
 
+use std::collections::BTreeMap;
+
+use crate::{
+    amount::{Amount, SCALE},
+    currency::Currency,
+    transaction::TransactionId,
+};
+
+pub type ClientId = u64;
+
+/// The number of the most recent transitions kept by a client, every copy of a client
+/// (in the stores and in the journal) carries them along
+pub const MAX_TRANSITIONS: usize = 16;
+
 #[derive(Debug, Error)]
 pub enum Error {
-    /// An attempt to modify a frozen account
+    /// An attempt to move funds or to open a dispute on a locked account
     ClientLocked,
+    /// An attempt to modify a closed account
+    ClientClosed,
+    /// An attempt to unlock an account that isn't locked
+    ClientNotLocked,
     /// An attempt to withdraw more than there is `available`
     WithdrawInsufficientFunds,
     /// An attempt to dispute more than there is `available`
     DisputeInsufficientFunds,
+    /// The resulting balance doesn't fit into `Amount`
+    BalanceOverflow,
 }
 
+impl Error {
+    /// The stable machine-readable code of the error
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ClientLocked => "client.client_locked",
+            Self::ClientClosed => "client.client_closed",
+            Self::ClientNotLocked => "client.client_not_locked",
+            Self::WithdrawInsufficientFunds => "client.withdraw_insufficient_funds",
+            Self::DisputeInsufficientFunds => "client.dispute_insufficient_funds",
+            Self::BalanceOverflow => "client.balance_overflow",
+        }
+    }
+}
+
+/// A row of the output, the same fields are written in every format of `format::OutputFormat`
+/// with the amounts as decimal strings
 #[allow(clippy::module_name_repetitions)]
+#[derive(Debug, PartialEq, Eq)]
 pub struct ClientCSV {
-    pub id: u16,
-    available: f32,
-    held: f32,
+    pub id: ClientId,
+    pub currency: Currency,
+    /// The column is only there when the balances in several currencies are written,
+    /// so that the accounts in the default currency keep the original layout
+    currency_column: bool,
+    available: Amount,
+    held: Amount,
     locked: bool,
+    /// The column is only there when negative balances are allowed
+    collections: Option<bool>,
+    /// The column is only there when the administrative actions are accepted, it tells
+    /// a closed account from an active one
+    status: Option<Status>,
+    /// The number of decimal places of the amounts
+    precision: usize,
+}
+
+impl ClientCSV {
+    /// Writes the amounts with `precision` decimal places rather than `amount::SCALE`,
+    /// rounding half away from zero
+    #[must_use]
+    pub fn with_precision(self, precision: usize) -> Self {
+        Self { precision, ..self }
+    }
+
+    /// Adds the currency of the balance
+    #[must_use]
+    pub fn with_currency(self) -> Self {
+        Self {
+            currency_column: true,
+            ..self
+        }
+    }
+
+    /// Adds the status of the account, i.e. whether it's active, locked or closed
+    #[must_use]
+    pub fn with_status(self, status: Status) -> Self {
+        Self {
+            status: Some(status),
+            ..self
+        }
+    }
+
+    /// Flags the account for collections when its `available` balance is negative
+    #[must_use]
+    pub fn with_collections(self) -> Self {
+        Self {
+            collections: Some(self.available.is_negative()),
+            ..self
+        }
+    }
 }
 
 impl Serialize for ClientCSV {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut state = serializer.serialize_struct("ClientCSV", 5)?;
+        let fields = 5
+            + usize::from(self.currency_column)
+            + usize::from(self.collections.is_some())
+            + usize::from(self.status.is_some());
+        let mut state = serializer.serialize_struct("ClientCSV", fields)?;
         state.serialize_field("client", &self.id)?;
-        state.serialize_field("available", &format!("{:.4}", self.available))?;
-        state.serialize_field("held", &format!("{:.4}", self.held))?;
-        state.serialize_field("total", &format!("{:.4}", (self.available + self.held)))?;
+        if self.currency_column {
+            state.serialize_field("currency", &self.currency)?;
+        }
+        // the total is the sum of the rounded balances, so that the row adds up as written
+        let precision = self.precision;
+        let overflow = || S::Error::custom("Total balance overflow");
+        let available = self
+            .available
+            .checked_round(precision)
+            .ok_or_else(overflow)?;
+        let held = self.held.checked_round(precision).ok_or_else(overflow)?;
+        let total = available.checked_add(held).ok_or_else(overflow)?;
+        state.serialize_field("available", &format!("{available:.precision$}"))?;
+        state.serialize_field("held", &format!("{held:.precision$}"))?;
+        state.serialize_field("total", &format!("{total:.precision$}"))?;
         state.serialize_field("locked", &self.locked)?;
+        if let Some(collections) = self.collections {
+            state.serialize_field("collections", &collections)?;
+        }
+        if let Some(status) = self.status {
+            state.serialize_field("status", &status)?;
+        }
         state.end()
     }
 }
 
-impl From<&Client> for ClientCSV {
-    fn from(client: &Client) -> Self {
-        Self {
-            id: client.id,
-            available: client.available,
-            held: client.held,
-            locked: client.locked,
-        }
-    }
+/// The state of an account as far as the administrative actions are concerned
+#[derive(Clone, Copy, Debug, PartialEq, Eq, SerializeMacro, Deserialize)]
+pub enum Status {
+    Active,
+    Locked,
+    Closed,
+}
+
+/// A change of the `Status` made by an administrative action
+#[derive(Clone, Debug, PartialEq, Eq, SerializeMacro, Deserialize)]
+pub struct Transition {
+    /// The ID given in the row of the administrative action
+    pub transaction_id: TransactionId,
+    pub from: Status,
+    pub to: Status,
+}
+
+/// The funds of a client in a single currency
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, SerializeMacro, Deserialize)]
+pub struct Balance {
+    pub available: Amount,
+    pub held: Amount,
 }
 
-#[derive(Clone, Debug, SerializeMacro, Deserialize)]
+/// The lock and the status are shared by all the currencies of the client
+#[derive(Clone, Debug, PartialEq, Eq, SerializeMacro, Deserialize)]
 pub struct Client {
-    id: u16,
-    available: f32,
-    held: f32,
+    id: ClientId,
+    balances: BTreeMap<Currency, Balance>,
     locked: bool,
+    closed: bool,
+    transitions: Vec<Transition>,
 }
 
 impl Client {
     #[must_use]
-    pub fn new(id: u16) -> Self {
+    pub fn new(id: ClientId) -> Self {
         Self {
             id,
-            available: 0.0,
-            held: 0.0,
+            balances: BTreeMap::new(),
             locked: false,
+            closed: false,
+            transitions: vec![],
         }
     }
 
     #[must_use]
-    pub fn id(&self) -> u16 {
+    pub fn id(&self) -> ClientId {
         self.id
     }
 
+    /// Zero in a currency the client has never had funds in
+    #[must_use]
+    pub fn available(&self, currency: &Currency) -> Amount {
+        self.balances
+            .get(currency)
+            .map_or(Amount::ZERO, |balance| balance.available)
+    }
+
+    /// Zero in a currency the client has never had funds in
+    #[must_use]
+    pub fn held(&self, currency: &Currency) -> Amount {
+        self.balances
+            .get(currency)
+            .map_or(Amount::ZERO, |balance| balance.held)
+    }
+
+    /// The balances per currency ordered by currency
+    pub fn balances(&self) -> impl Iterator<Item = (&Currency, &Balance)> + '_ {
+        self.balances.iter()
+    }
+
+    /// A row of the output per currency ordered by currency
+    pub fn clients_csv(&self) -> impl Iterator<Item = ClientCSV> + '_ {
+        self.balances.iter().map(|(currency, balance)| ClientCSV {
+            id: self.id,
+            currency: currency.clone(),
+            currency_column: false,
+            available: balance.available,
+            held: balance.held,
+            locked: self.locked,
+            collections: None,
+            status: None,
+            precision: SCALE,
+        })
+    }
+
+    /// Whether the account has been locked by a chargeback, a closed account stays locked
+    #[must_use]
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    #[must_use]
+    pub fn status(&self) -> Status {
+        if self.closed {
+            Status::Closed
+        } else if self.locked {
+            Status::Locked
+        } else {
+            Status::Active
+        }
+    }
+
+    /// The last `MAX_TRANSITIONS` changes of the status made by the administrative actions,
+    /// oldest first
+    #[must_use]
+    pub fn transitions(&self) -> &[Transition] {
+        &self.transitions
+    }
+
     /// # Errors
-    pub fn deposit(&mut self, amount: f32) -> Result<(), Error> {
-        if self.locked {
-            Err(Error::ClientLocked)
+    pub fn deposit(&mut self, currency: &Currency, amount: Amount) -> Result<(), Error> {
+        if let Some(error) = self.frozen() {
+            Err(error)
         } else {
-            self.available += amount;
+            let balance = self.balance_mut(currency);
+            balance.available = add(balance.available, amount)?;
             Ok(())
         }
     }
 
     /// # Errors
-    pub fn withdraw(&mut self, amount: f32) -> Result<(), Error> {
-        if self.locked {
-            Err(Error::ClientLocked)
-        } else if amount > self.available {
+    pub fn withdraw(&mut self, currency: &Currency, amount: Amount) -> Result<(), Error> {
+        if let Some(error) = self.frozen() {
+            Err(error)
+        } else if amount > self.available(currency) {
             Err(Error::WithdrawInsufficientFunds)
         } else {
-            self.available -= amount;
+            let balance = self.balance_mut(currency);
+            balance.available = sub(balance.available, amount)?;
             Ok(())
         }
     }
 
     /// # Errors
-    pub fn dispute(&mut self, amount: f32) -> Result<(), Error> {
-        if self.locked {
-            Err(Error::ClientLocked)
-        } else if amount > self.available {
+    pub fn dispute(&mut self, currency: &Currency, amount: Amount) -> Result<(), Error> {
+        if let Some(error) = self.frozen() {
+            Err(error)
+        } else if amount > self.available(currency) {
             Err(Error::DisputeInsufficientFunds)
         } else {
-            self.available -= amount;
-            self.held += amount;
+            let balance = self.balance_mut(currency);
+            balance.available = sub(balance.available, amount)?;
+            balance.held = add(balance.held, amount)?;
             Ok(())
         }
     }
 
+    /// Same as `dispute`, but `available` may go negative, e.g. when a deposit is disputed
+    /// after it has been withdrawn
+    ///
     /// # Errors
-    pub fn resolve(&mut self, amount: f32) -> Result<(), Error> {
-        if self.locked {
-            Err(Error::ClientLocked)
+    pub fn dispute_overdrawn(&mut self, currency: &Currency, amount: Amount) -> Result<(), Error> {
+        if let Some(error) = self.frozen() {
+            Err(error)
         } else {
-            self.available += amount;
-            self.held -= amount;
+            let balance = self.balance_mut(currency);
+            balance.available = sub(balance.available, amount)?;
+            balance.held = add(balance.held, amount)?;
             Ok(())
         }
     }
 
     /// # Errors
-    pub fn chargeback(&mut self, amount: f32) -> Result<(), Error> {
-        if self.locked {
-            Err(Error::ClientLocked)
+    pub fn resolve(&mut self, currency: &Currency, amount: Amount) -> Result<(), Error> {
+        if let Some(error) = self.settling() {
+            Err(error)
         } else {
+            let balance = self.balance_mut(currency);
+            balance.available = add(balance.available, amount)?;
+            balance.held = sub(balance.held, amount)?;
+            Ok(())
+        }
+    }
+
+    /// # Errors
+    pub fn chargeback(&mut self, currency: &Currency, amount: Amount) -> Result<(), Error> {
+        if let Some(error) = self.settling() {
+            Err(error)
+        } else {
+            let balance = self.balance_mut(currency);
+            balance.held = sub(balance.held, amount)?;
             self.locked = true;
-            self.held -= amount;
             Ok(())
         }
     }
+
+    /// Returns the funds of a charged back Transfer to the sender, even if the account of the
+    /// sender has been locked or closed since, as the funds are owed to them either way
+    ///
+    /// # Errors
+    pub fn refund(&mut self, currency: &Currency, amount: Amount) -> Result<(), Error> {
+        let balance = self.balance_mut(currency);
+        balance.available = add(balance.available, amount)?;
+        Ok(())
+    }
+
+    /// Reverses a disputed Withdrawal: the withdrawn amount is held until the dispute is settled
+    ///
+    /// # Errors
+    pub fn dispute_withdrawal(&mut self, currency: &Currency, amount: Amount) -> Result<(), Error> {
+        if let Some(error) = self.frozen() {
+            Err(error)
+        } else {
+            let balance = self.balance_mut(currency);
+            balance.held = add(balance.held, amount)?;
+            Ok(())
+        }
+    }
+
+    /// The Withdrawal stands, so the held amount is dropped
+    ///
+    /// # Errors
+    pub fn resolve_withdrawal(&mut self, currency: &Currency, amount: Amount) -> Result<(), Error> {
+        if let Some(error) = self.settling() {
+            Err(error)
+        } else {
+            let balance = self.balance_mut(currency);
+            balance.held = sub(balance.held, amount)?;
+            Ok(())
+        }
+    }
+
+    /// The Withdrawal is reversed, so the held amount is released back to `available`,
+    /// the client isn't locked since the funds are returned to them rather than taken away
+    ///
+    /// # Errors
+    pub fn chargeback_withdrawal(
+        &mut self,
+        currency: &Currency,
+        amount: Amount,
+    ) -> Result<(), Error> {
+        if let Some(error) = self.settling() {
+            Err(error)
+        } else {
+            let balance = self.balance_mut(currency);
+            balance.held = sub(balance.held, amount)?;
+            balance.available = add(balance.available, amount)?;
+            Ok(())
+        }
+    }
+
+    /// Reinstates a locked account after a review
+    ///
+    /// # Errors
+    pub fn unlock(&mut self, transaction_id: TransactionId) -> Result<(), Error> {
+        if self.closed {
+            Err(Error::ClientClosed)
+        } else if self.locked {
+            self.transition(transaction_id, |client| client.locked = false);
+            Ok(())
+        } else {
+            Err(Error::ClientNotLocked)
+        }
+    }
+
+    /// Closes the account for good, whether it's locked or not
+    ///
+    /// # Errors
+    pub fn close(&mut self, transaction_id: TransactionId) -> Result<(), Error> {
+        if self.closed {
+            Err(Error::ClientClosed)
+        } else {
+            self.transition(transaction_id, |client| client.closed = true);
+            Ok(())
+        }
+    }
+
+    fn transition(&mut self, transaction_id: TransactionId, change: impl FnOnce(&mut Self)) {
+        let from = self.status();
+        change(self);
+        if self.transitions.len() == MAX_TRANSITIONS {
+            self.transitions.remove(0);
+        }
+        self.transitions.push(Transition {
+            transaction_id,
+            from,
+            to: self.status(),
+        });
+    }
+
+    /// Any operation on a currency opens a balance in it
+    fn balance_mut(&mut self, currency: &Currency) -> &mut Balance {
+        self.balances.entry(currency.clone()).or_default()
+    }
+
+    /// Nothing new may happen to a locked or a closed account
+    fn frozen(&self) -> Option<Error> {
+        if self.closed {
+            Some(Error::ClientClosed)
+        } else if self.locked {
+            Some(Error::ClientLocked)
+        } else {
+            None
+        }
+    }
+
+    /// The disputes opened before the account has been locked, e.g. the other slices of
+    /// a transaction one slice of which has been charged back, may still be settled
+    fn settling(&self) -> Option<Error> {
+        self.closed.then_some(Error::ClientClosed)
+    }
+}
+
+fn add(balance: Amount, amount: Amount) -> Result<Amount, Error> {
+    balance.checked_add(amount).ok_or(Error::BalanceOverflow)
+}
+
+fn sub(balance: Amount, amount: Amount) -> Result<Amount, Error> {
+    balance.checked_sub(amount).ok_or(Error::BalanceOverflow)
 }
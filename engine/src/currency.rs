@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The code of a currency isn't 1 to 8 ASCII letters or digits
+    Malformed,
+}
+
+/// The maximum length of a code
+const MAX_LEN: usize = 8;
+
+/// The code of a currency, e.g. `USD`, case-insensitive and kept in upper case
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Currency(String);
+
+impl Default for Currency {
+    /// The currency of the rows that don't specify one, unless configured otherwise
+    fn default() -> Self {
+        Self("USD".to_string())
+    }
+}
+
+impl FromStr for Currency {
+    type Err = Error;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        let code = code.trim();
+        if (1..=MAX_LEN).contains(&code.len()) && code.chars().all(|c| c.is_ascii_alphanumeric()) {
+            Ok(Self(code.to_ascii_uppercase()))
+        } else {
+            Err(Error::Malformed)
+        }
+    }
+}
+
+impl TryFrom<String> for Currency {
+    type Error = Error;
+
+    fn try_from(code: String) -> Result<Self, Self::Error> {
+        code.parse()
+    }
+}
+
+impl From<Currency> for String {
+    fn from(currency: Currency) -> Self {
+        currency.0
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
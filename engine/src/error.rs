@@ -0,0 +1,39 @@
+use crate::{client, ledger, processor, transaction};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The record is rejected by the client's account
+    Client(client::Error),
+    /// The record is rejected by the referenced transaction
+    Transaction(transaction::Error),
+    /// The record is rejected by the processor
+    Processor(processor::Error),
+    /// The record is rejected by the ledger
+    Ledger(ledger::Error),
+    /// The underlying store failed
+    Store(store::error::Error),
+}
+
+impl Error {
+    /// The stable machine-readable code of the error, e.g. `client.withdraw_insufficient_funds`
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Client(error) => error.code(),
+            Self::Transaction(error) => error.code(),
+            Self::Processor(error) => error.code(),
+            Self::Ledger(error) => error.code(),
+            Self::Store(_) => "store.failure",
+        }
+    }
+
+    /// A failure of the store leaves the processor in an unknown state, so the processing
+    /// shouldn't be continued. All the other errors are rejections of a single record
+    #[must_use]
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Self::Store(_) | Self::Processor(processor::Error::ShardStopped)
+        )
+    }
+}
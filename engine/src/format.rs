@@ -0,0 +1,325 @@
+use csv::StringRecord;
+use serde::{Deserialize, Serialize, Serializer as _};
+use serde_json::{ser::PrettyFormatter, Map, Serializer, Value};
+use std::{
+    error::Error as StdError,
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    sync::Arc,
+};
+
+use crate::{
+    input::Record,
+    write_csv::{write_csv_headers, write_output, Output},
+};
+
+/// A CSV row that holds the name of the column of the action in that very column,
+/// which is never a valid action, is a header row
+const ACTION_COLUMN: &str = "type";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// A CSV row can't be read or parsed into a record
+    Csv(csv::Error),
+    /// A CSV row has a different number of fields than its header row
+    UnequalLengths,
+    /// A line of JSON can't be parsed into a record
+    Json(serde_json::Error),
+    /// A line can't be read
+    Io(io::Error),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputFormat {
+    /// A header row followed by a row per record
+    Csv,
+    /// A JSON object per line, the blank lines are skipped
+    JsonLines,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Csv,
+    /// A JSON object per line
+    JsonLines,
+    /// A single indented JSON array
+    Json,
+}
+
+/// A row of the input that can't be parsed into a record
+#[derive(Debug)]
+pub struct Malformed {
+    /// The name given to the input by `Records::with_input`
+    pub input: Option<Arc<str>>,
+    pub line: Option<u64>,
+    /// The names of the fields that apply to the row
+    pub headers: Arc<StringRecord>,
+    /// The original fields of the row, if it could be read at all
+    pub fields: Option<StringRecord>,
+    pub error: Error,
+}
+
+/// A record along with the row of the input it's been parsed from
+#[derive(Clone, Debug)]
+pub struct Row {
+    pub record: Record,
+    pub source: Source,
+}
+
+/// The original row of the input
+#[derive(Clone, Debug)]
+pub struct Source {
+    /// The name given to the input by `Records::with_input`
+    pub input: Option<Arc<str>>,
+    pub line: u64,
+    /// The names of the fields that apply to the row, the header rows in the middle of
+    /// the input may change them
+    pub headers: Arc<StringRecord>,
+    pub fields: StringRecord,
+}
+
+/// The records of the input in their original order
+pub struct Records {
+    input: Option<Arc<str>>,
+    headers: StringRecord,
+    rows: Box<dyn Iterator<Item = Result<Row, Malformed>>>,
+}
+
+impl Records {
+    /// # Errors
+    /// Fs-related errors of opening the file, or the header row of a CSV file can't be read
+    pub fn open(path: &str, format: InputFormat) -> Result<Self, Error> {
+        Ok(Self::from_reader(File::open(path)?, format)?.with_input(path))
+    }
+
+    /// # Errors
+    /// The header row of a CSV input can't be read
+    pub fn from_reader(reader: impl Read + 'static, format: InputFormat) -> Result<Self, Error> {
+        Self::read(reader, format, None)
+    }
+
+    /// Same as `from_reader`, but a CSV input may omit the header row, then the headers of
+    /// `previous` apply, e.g. to the next file of a batch split into several files
+    ///
+    /// # Errors
+    pub fn continued(
+        reader: impl Read + 'static,
+        format: InputFormat,
+        previous: &Self,
+    ) -> Result<Self, Error> {
+        Self::read(reader, format, Some(previous.headers.clone()))
+    }
+
+    /// Names the input in the sources of the rows, e.g. by the path of the file
+    #[must_use]
+    pub fn with_input(self, input: &str) -> Self {
+        Self {
+            input: Some(input.into()),
+            ..self
+        }
+    }
+
+    fn read(
+        reader: impl Read + 'static,
+        format: InputFormat,
+        headers: Option<StringRecord>,
+    ) -> Result<Self, Error> {
+        match format {
+            InputFormat::Csv => {
+                // the concatenated files may have different headers, hence the number of
+                // the fields is checked against the header row that applies to the row
+                let mut reader = csv::ReaderBuilder::new()
+                    .has_headers(headers.is_none())
+                    .flexible(true)
+                    .from_reader(reader);
+                let headers = if let Some(headers) = headers {
+                    headers
+                } else {
+                    reader.headers()?.clone()
+                };
+                let mut row_headers = Arc::new(headers.clone());
+                Ok(Self {
+                    input: None,
+                    headers,
+                    rows: Box::new(reader.into_records().filter_map(move |row| {
+                        // the header rows of the files concatenated to this one are skipped
+                        if let Ok(fields) = &row {
+                            if is_header(&row_headers, fields) {
+                                row_headers = Arc::new(fields.clone());
+                                return None;
+                            }
+                        }
+                        Some(csv_record(&row_headers, row))
+                    })),
+                })
+            }
+            InputFormat::JsonLines => {
+                // the whole line is reported as a single field
+                let headers = StringRecord::from(vec!["record"]);
+                let row_headers = Arc::new(headers.clone());
+                Ok(Self {
+                    input: None,
+                    headers,
+                    rows: Box::new(
+                        (1_u64..)
+                            .zip(BufReader::new(reader).lines())
+                            .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()))
+                            .map(move |(line, text)| json_record(&row_headers, line, text)),
+                    ),
+                })
+            }
+        }
+    }
+
+    /// The header row at the start of the input, see `Source::headers` for those of a row
+    #[must_use]
+    pub fn headers(&self) -> &StringRecord {
+        &self.headers
+    }
+}
+
+impl Iterator for Records {
+    type Item = Result<Row, Malformed>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let input = self.input.as_ref();
+        self.rows.next().map(|row| {
+            row.map(|mut row| {
+                row.source.input = input.cloned();
+                row
+            })
+            .map_err(|mut malformed| {
+                malformed.input = input.cloned();
+                malformed
+            })
+        })
+    }
+}
+
+/// Writes `data` in the format, e.g. the accounts or the trial balance of the ledger.
+/// The amounts are written as decimal strings in every format, so that they're never
+/// rounded by a float parser on the other end. The header row of CSV is omitted unless
+/// `headers` is set
+///
+/// # Errors
+pub fn write<T: Serialize, D: Iterator<Item = T>>(
+    output: &Output<'_>,
+    format: OutputFormat,
+    headers: bool,
+    data: D,
+) -> Result<(), Box<dyn StdError>> {
+    match format {
+        OutputFormat::Csv => write_csv_headers(output, headers, data),
+        OutputFormat::JsonLines => write_output(output, |writer| {
+            for item in data {
+                serde_json::to_writer(&mut *writer, &item)?;
+                writer.write_all(b"\n")?;
+            }
+            Ok(())
+        }),
+        OutputFormat::Json => write_output(output, |writer| {
+            // the items are streamed into the array rather than collected first
+            Serializer::with_formatter(&mut *writer, PrettyFormatter::with_indent(b"  "))
+                .collect_seq(data)?;
+            Ok(writer.write_all(b"\n")?)
+        }),
+    }
+}
+
+/// The headers that lack the column of the action are only matched field by field
+fn is_header(headers: &StringRecord, fields: &StringRecord) -> bool {
+    fields == headers
+        || headers
+            .iter()
+            .position(|header| header == ACTION_COLUMN)
+            .and_then(|position| fields.get(position))
+            == Some(ACTION_COLUMN)
+}
+
+fn csv_record(
+    headers: &Arc<StringRecord>,
+    row: csv::Result<StringRecord>,
+) -> Result<Row, Malformed> {
+    let malformed = |line, fields, error| Malformed {
+        input: None,
+        line,
+        headers: Arc::clone(headers),
+        fields,
+        error,
+    };
+    match row {
+        Ok(fields) if fields.len() != headers.len() => Err(malformed(
+            fields.position().map(csv::Position::line),
+            Some(fields),
+            Error::UnequalLengths,
+        )),
+        Ok(fields) => {
+            let line = fields.position().map(csv::Position::line);
+            match fields.deserialize::<Record>(Some(headers)) {
+                Ok(record) => Ok(Row {
+                    record,
+                    source: Source {
+                        input: None,
+                        line: line.unwrap_or_default(),
+                        headers: Arc::clone(headers),
+                        fields,
+                    },
+                }),
+                Err(error) => Err(malformed(line, Some(fields), error.into())),
+            }
+        }
+        Err(error) => Err(malformed(
+            error.position().map(csv::Position::line),
+            None,
+            error.into(),
+        )),
+    }
+}
+
+fn json_record(
+    headers: &Arc<StringRecord>,
+    line: u64,
+    text: io::Result<String>,
+) -> Result<Row, Malformed> {
+    let malformed = |fields, error| Malformed {
+        input: None,
+        line: Some(line),
+        headers: Arc::clone(headers),
+        fields,
+        error,
+    };
+    let text = text.map_err(|error| malformed(None, error.into()))?;
+    let fields = StringRecord::from(vec![text.as_str()]);
+    parse_json(text.as_bytes())
+        .map(|record| Row {
+            record,
+            source: Source {
+                input: None,
+                line,
+                headers: Arc::clone(headers),
+                fields: fields.clone(),
+            },
+        })
+        .map_err(|error| malformed(Some(fields), error.into()))
+}
+
+/// Parses a single JSON object into a record, the object may span several lines
+///
+/// # Errors
+/// The text isn't a JSON object or the object isn't a valid record
+pub fn parse_json(text: &[u8]) -> Result<Record, serde_json::Error> {
+    serde_json::from_slice::<Map<String, Value>>(text)
+        .and_then(|object| Record::deserialize(Value::Object(stringify_numbers(object))))
+}
+
+/// The numbers are turned into their original text, the same as the fields of a CSV row,
+/// so that the amounts and the IDs are parsed exactly rather than through a float
+fn stringify_numbers(object: Map<String, Value>) -> Map<String, Value> {
+    object
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::Number(number) => (key, Value::String(number.to_string())),
+            value => (key, value),
+        })
+        .collect()
+}
@@ -1,26 +1,110 @@
-use serde::Deserialize;
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use std::{fmt, num::IntErrorKind};
 
-#[derive(Debug, Deserialize)]
+use crate::{amount::Amount, client::ClientId, currency::Currency, transaction::TransactionId};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Action {
     #[serde(rename = "deposit")]
     Deposit,
     #[serde(rename = "withdrawal")]
     Withdrawal,
+    #[serde(rename = "transfer")]
+    Transfer,
     #[serde(rename = "dispute")]
     Dispute,
     #[serde(rename = "resolve")]
     Resolve,
     #[serde(rename = "chargeback")]
     ChargeBack,
+    /// Administrative: reinstates a locked account
+    #[serde(rename = "unlock")]
+    Unlock,
+    /// Administrative: closes an account
+    #[serde(rename = "close")]
+    Close,
+}
+
+impl Action {
+    /// The administrative actions come from the support team rather than from the clients,
+    /// hence they're only accepted by a processor in the admin-input mode
+    #[must_use]
+    pub fn is_admin(&self) -> bool {
+        matches!(self, Self::Unlock | Self::Close)
+    }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Record {
     #[serde(rename = "type")]
     pub action: Action,
-    #[serde(rename = "client")]
-    pub client_id: u16,
-    #[serde(rename = "tx")]
-    pub transaction_id: u32,
-    pub amount: Option<f32>,
+    #[serde(rename = "client", deserialize_with = "id")]
+    pub client_id: ClientId,
+    #[serde(rename = "tx", deserialize_with = "id")]
+    pub transaction_id: TransactionId,
+    pub amount: Option<Amount>,
+    /// The default currency of the processor applies when the column is empty or omitted,
+    /// so does an empty string of JSON Lines
+    #[serde(default, deserialize_with = "optional_currency")]
+    pub currency: Option<Currency>,
+    /// Only a Transfer has a recipient, the column may be omitted altogether
+    #[serde(rename = "to_client", default, deserialize_with = "optional_id")]
+    pub recipient_id: Option<ClientId>,
+}
+
+/// The IDs are parsed by hand in human-readable formats, so that an ID that doesn't fit
+/// is reported as such rather than as a generic number error
+struct Id(u64);
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(IdVisitor)
+        } else {
+            u64::deserialize(deserializer).map(Self)
+        }
+    }
+}
+
+struct IdVisitor;
+
+impl Visitor<'_> for IdVisitor {
+    type Value = Id;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "an ID from 0 to {}", u64::MAX)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        value.trim().parse().map(Id).map_err(|error| {
+            if let IntErrorKind::PosOverflow = error.kind() {
+                E::custom(format!(
+                    "ID {value:?} overflows, the maximum is {}",
+                    u64::MAX
+                ))
+            } else {
+                E::custom(format!("invalid ID {value:?}: {error}"))
+            }
+        })
+    }
+}
+
+fn id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    Id::deserialize(deserializer).map(|id| id.0)
+}
+
+fn optional_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    Option::<Id>::deserialize(deserializer).map(|id| id.map(|id| id.0))
+}
+
+fn optional_currency<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Currency>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(code) if !code.trim().is_empty() => code.parse().map(Some).map_err(de::Error::custom),
+        _ => Ok(None),
+    }
 }
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    client::{Balance, Client, ClientId, Status},
+    currency::Currency,
+    input::Record,
+    transaction::Transaction,
+};
+use store::{error::Error, store::Store};
+
+/// The journal is keyed by the sequence number of the entries, starting from 0
+pub type Journal = Box<dyn Store<Sequence, JournalEntry> + Send>;
+
+/// The sequence number of an entry, serialized big-endian so that the keys of the database
+/// sort in the order the entries have been appended in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sequence(pub u64);
+
+impl Serialize for Sequence {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.to_be_bytes().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Sequence {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <[u8; 8]>::deserialize(deserializer).map(|bytes| Self(u64::from_be_bytes(bytes)))
+    }
+}
+
+/// An accepted record along with the states of the transaction and the clients
+/// it has resulted in
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub record: Record,
+    /// `None` for the administrative actions, which don't refer to a transaction
+    pub transaction: Option<Transaction>,
+    pub clients: Vec<ClientState>,
+}
+
+/// The part of a client that a record may change: the status and the balance in the currency
+/// of the transaction. The history of the transitions isn't copied into every entry
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientState {
+    pub id: ClientId,
+    pub status: Status,
+    /// `None` for the administrative actions, which don't touch the balances
+    pub balance: Option<(Currency, Balance)>,
+}
+
+impl ClientState {
+    #[must_use]
+    pub fn new(client: &Client, currency: Option<&Currency>) -> Self {
+        Self {
+            id: client.id(),
+            status: client.status(),
+            balance: currency.map(|currency| {
+                (
+                    currency.clone(),
+                    Balance {
+                        available: client.available(currency),
+                        held: client.held(currency),
+                    },
+                )
+            }),
+        }
+    }
+}
+
+/// The entries of the journal in the order they've been appended in, one at a time. The sequence
+/// numbers are contiguous, so the entries are looked up one by one rather than sorted
+pub fn entries(
+    journal: &mut dyn Store<Sequence, JournalEntry>,
+) -> impl Iterator<Item = Result<(Sequence, JournalEntry), Error>> + '_ {
+    (0..).map_while(move |sequence| {
+        let sequence = Sequence(sequence);
+        journal
+            .get(&sequence)
+            .map(|entry| entry.map(|entry| (sequence, entry.clone())))
+            .transpose()
+    })
+}
@@ -0,0 +1,253 @@
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt};
+
+use crate::{
+    amount::Amount,
+    client::{Client, ClientId},
+    currency::Currency,
+    input::Action,
+    transaction::{Action as TransactionAction, Transaction},
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// A sum of the postings doesn't fit into `Amount`
+    Overflow,
+    /// The debits don't equal the credits
+    Unbalanced,
+    /// The balances of a client differ from those of the client's accounts in the ledger
+    ClientMismatch,
+}
+
+impl Error {
+    /// The stable machine-readable code of the error
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Overflow => "ledger.overflow",
+            Self::Unbalanced => "ledger.unbalanced",
+            Self::ClientMismatch => "ledger.client_mismatch",
+        }
+    }
+}
+
+/// The customer accounts are liabilities, so their balance is the credit minus the debit
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Account {
+    CustomerAvailable(ClientId),
+    CustomerHeld(ClientId),
+    /// Where the deposits come from and the withdrawals go to
+    FundingSource,
+    /// The withdrawals reversed by disputes, which are covered by the platform
+    ChargebackLoss,
+}
+
+impl fmt::Display for Account {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CustomerAvailable(id) => write!(f, "customer-available:{id}"),
+            Self::CustomerHeld(id) => write!(f, "customer-held:{id}"),
+            Self::FundingSource => write!(f, "funding-source"),
+            Self::ChargebackLoss => write!(f, "chargeback-loss"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Posting {
+    pub currency: Currency,
+    pub debit: Account,
+    pub credit: Account,
+    pub amount: Amount,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Balance {
+    pub debit: Amount,
+    pub credit: Amount,
+}
+
+impl Balance {
+    fn add(self, debit: Amount, credit: Amount) -> Result<Self, Error> {
+        Ok(Self {
+            debit: self.debit.checked_add(debit).ok_or(Error::Overflow)?,
+            credit: self.credit.checked_add(credit).ok_or(Error::Overflow)?,
+        })
+    }
+
+    fn credit_balance(self) -> Result<Amount, Error> {
+        self.credit.checked_sub(self.debit).ok_or(Error::Overflow)
+    }
+
+    fn debit_balance(self) -> Result<Amount, Error> {
+        self.debit.checked_sub(self.credit).ok_or(Error::Overflow)
+    }
+}
+
+/// A row of the trial balance
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Serialize)]
+pub struct LedgerCSV {
+    pub currency: Currency,
+    pub account: String,
+    pub debit: Amount,
+    pub credit: Amount,
+}
+
+/// The accounts in a single currency
+#[derive(Default)]
+struct Book {
+    accounts: BTreeMap<Account, Balance>,
+    total: Balance,
+}
+
+/// The totals of the postings per currency and account, the postings themselves aren't kept.
+/// Every currency has a separate set of accounts that balances on its own
+#[derive(Default)]
+pub struct Ledger {
+    books: BTreeMap<Currency, Book>,
+}
+
+impl Ledger {
+    /// Either the posting is applied fully or not at all
+    ///
+    /// # Errors
+    pub fn post(&mut self, posting: Posting) -> Result<(), Error> {
+        let book = self.books.entry(posting.currency).or_default();
+        let debit = book
+            .balance(posting.debit)
+            .add(posting.amount, Amount::ZERO)?;
+        let credit = book
+            .balance(posting.credit)
+            .add(Amount::ZERO, posting.amount)?;
+        let total = book.total.add(posting.amount, posting.amount)?;
+
+        book.accounts.insert(posting.debit, debit);
+        book.accounts.insert(posting.credit, credit);
+        book.total = total;
+        Ok(())
+    }
+
+    #[must_use]
+    pub fn balance(&self, currency: &Currency, account: Account) -> Balance {
+        self.books
+            .get(currency)
+            .map(|book| book.balance(account))
+            .unwrap_or_default()
+    }
+
+    /// The totals of all the postings in the currency
+    #[must_use]
+    pub fn total(&self, currency: &Currency) -> Balance {
+        self.books
+            .get(currency)
+            .map(|book| book.total)
+            .unwrap_or_default()
+    }
+
+    /// Confirms in every currency that the debits equal the credits over all the accounts and
+    /// the totals of the postings, and that the funds owed to the customers, i.e. the signed
+    /// balances of their accounts, are what has come in through the funding source net of
+    /// the losses covered by the platform
+    ///
+    /// # Errors
+    pub fn trial_balance(&self) -> Result<(), Error> {
+        for book in self.books.values() {
+            let mut total = Balance::default();
+            let mut customers = Amount::ZERO;
+            for (account, balance) in &book.accounts {
+                total = total.add(balance.debit, balance.credit)?;
+                if let Account::CustomerAvailable(_) | Account::CustomerHeld(_) = account {
+                    customers = customers
+                        .checked_add(balance.credit_balance()?)
+                        .ok_or(Error::Overflow)?;
+                }
+            }
+            let funded = book
+                .balance(Account::FundingSource)
+                .debit_balance()?
+                .checked_add(book.balance(Account::ChargebackLoss).debit_balance()?)
+                .ok_or(Error::Overflow)?;
+            if total != book.total || total.debit != total.credit || customers != funded {
+                return Err(Error::Unbalanced);
+            }
+        }
+        Ok(())
+    }
+
+    /// Confirms that the balances of the client equal those of the client's accounts
+    /// in every currency
+    ///
+    /// # Errors
+    pub fn reconcile(&self, client: &Client) -> Result<(), Error> {
+        let currencies = client
+            .balances()
+            .map(|(currency, _)| currency)
+            .chain(self.books.keys());
+        for currency in currencies {
+            let available = self
+                .balance(currency, Account::CustomerAvailable(client.id()))
+                .credit_balance()?;
+            let held = self
+                .balance(currency, Account::CustomerHeld(client.id()))
+                .credit_balance()?;
+            if available != client.available(currency) || held != client.held(currency) {
+                return Err(Error::ClientMismatch);
+            }
+        }
+        Ok(())
+    }
+
+    /// The trial balance ordered by currency and account
+    pub fn ledger_csv(&self) -> impl Iterator<Item = LedgerCSV> + '_ {
+        self.books.iter().flat_map(|(currency, book)| {
+            book.accounts.iter().map(|(account, balance)| LedgerCSV {
+                currency: currency.clone(),
+                account: account.to_string(),
+                debit: balance.debit,
+                credit: balance.credit,
+            })
+        })
+    }
+}
+
+impl Book {
+    fn balance(&self, account: Account) -> Balance {
+        self.accounts.get(&account).copied().unwrap_or_default()
+    }
+}
+
+/// The posting of an accepted record that refers to the `transaction`, where `amount` is what
+/// the record has actually moved, e.g. a slice of a partial dispute.
+///
+/// The funds of a Deposit or a Transfer are held at the client who has received them, while
+/// a disputed Withdrawal is held at the client who has made it on the account of the platform
+#[must_use]
+pub fn posting(action: &Action, transaction: &Transaction, amount: Amount) -> Option<Posting> {
+    use Account::{ChargebackLoss, CustomerAvailable, CustomerHeld, FundingSource};
+
+    let client = transaction.client_id();
+    let holder = transaction.recipient_id().unwrap_or(client);
+    let withdrawal = transaction.action() == TransactionAction::Withdrawal;
+    let (debit, credit) = match action {
+        Action::Deposit => (FundingSource, CustomerAvailable(client)),
+        Action::Withdrawal => (CustomerAvailable(client), FundingSource),
+        Action::Transfer => (CustomerAvailable(client), CustomerAvailable(holder)),
+        Action::Dispute if withdrawal => (ChargebackLoss, CustomerHeld(holder)),
+        Action::Dispute => (CustomerAvailable(holder), CustomerHeld(holder)),
+        Action::Resolve if withdrawal => (CustomerHeld(holder), ChargebackLoss),
+        Action::Resolve => (CustomerHeld(holder), CustomerAvailable(holder)),
+        Action::ChargeBack if withdrawal => (CustomerHeld(holder), CustomerAvailable(holder)),
+        Action::ChargeBack if transaction.recipient_id().is_some() => {
+            (CustomerHeld(holder), CustomerAvailable(client))
+        }
+        Action::ChargeBack => (CustomerHeld(holder), FundingSource),
+        Action::Unlock | Action::Close => return None,
+    };
+    Some(Posting {
+        currency: transaction.currency().clone(),
+        debit,
+        credit,
+        amount,
+    })
+}
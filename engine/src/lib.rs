@@ -7,24 +7,52 @@
 //! The core implementation of the Payment Engine
 //!
 //! All exceptional situations (when the input data is considered invalid)
-//! are covered in three error enums:
+//! are covered in four error enums:
 //! * [Client-level errors](client/enum.Error.html)
 //! * [Transaction-level errors](transaction/enum.Error.html)
 //! * [Global processing errors](processor/enum.Error.html)
+//! * [Ledger errors](ledger/enum.Error.html)
+//!
+//! The four of them along with the failures of the underlying store are wrapped
+//! into the [top-level error](error/enum.Error.html) that `Processor` returns
 
 #[macro_use]
 extern crate derive_error;
 
+/// Implements the exact fixed-point decimal used for all amounts of funds
+pub mod amount;
+
 /// Implements the mutation and a serde-serializable representation
 /// of the client's account
 pub mod client;
 
+/// Implements the code of a currency the balances are kept in
+pub mod currency;
+
+/// Implements the readers of the input and the writers of the output in the supported formats
+pub mod format;
+
+/// Implements the top-level error that wraps all the errors of the engine
+pub mod error;
+
 /// Implements the serde-deserializable struct for a single row in the input
 pub mod input;
 
+/// Implements the append-only journal of the accepted records
+pub mod journal;
+
+/// Implements the double-entry ledger view of the movements of funds
+pub mod ledger;
+
 /// Implements the core validation and processing of transactions
 pub mod processor;
 
+/// Implements the parallel processing of transactions sharded by client
+pub mod sharded_processor;
+
+/// Implements the summary of the records processed by a run
+pub mod stats;
+
 /// Implements the mutation of a transaction
 pub mod transaction;
 
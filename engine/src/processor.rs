@@ -1,21 +1,30 @@
-use std::error::Error as StdError;
+use std::time::Instant;
 
 use crate::{
-    client::{Client, ClientCSV},
+    amount::Amount,
+    client::{Client, ClientCSV, ClientId},
+    currency::Currency,
+    error::Error as EngineError,
     input::{Action, Record},
-    transaction::{Action as TransactionAction, Transaction},
+    journal::{entries, ClientState, Journal, JournalEntry, Sequence},
+    ledger::{posting, Ledger},
+    stats::ProcessingStats,
+    transaction::{
+        Action as TransactionAction, Error as TransactionError, Transaction, TransactionId,
+    },
 };
 use store::store::Store;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    /// A transaction of type Deposit or Withdrawal doesn't specify the amount of funds
+    /// A transaction of type Deposit, Withdrawal or Transfer doesn't specify the amount of funds
     AmountUnspecified,
-    /// A transaction of type Dispute, Resolve or Chargeback *does* specify the amount of funds
+    /// An administrative action *does* specify the amount of funds
     AmountUnnecessary,
     /// The amount of funds specified is negative
     AmountNegative,
-    /// A transaction of type Deposit or Withdrawal with the ID that has been seen previously
+    /// A transaction of type Deposit, Withdrawal or Transfer with the ID that has been seen
+    /// previously
     TransactionIdDuplicate,
     /// A transaction of type Dispute, Resolve or Chargeback with the ID that hasn't been seen
     /// previously
@@ -26,128 +35,641 @@ pub enum Error {
     /// A transaction of type Dispute, Resolve or Chargeback with the client ID that can't be found
     // FIXME: perhaps this is the error of a higher order
     ClientNotFound,
+    /// A transaction of type Transfer doesn't specify the recipient
+    RecipientUnspecified,
+    /// A transaction of type other than Transfer *does* specify the recipient
+    RecipientUnnecessary,
+    /// A transaction of type Dispute, Resolve or Chargeback with the currency that is different
+    /// with that of the original transaction
+    CurrencyMismatch,
+    /// A transaction of type Transfer with the recipient being the sender
+    TransferToSelf,
+    /// A transaction of type Transfer between two clients that belong to different shards of
+    /// the `ShardedProcessor`, which can't be applied atomically
+    TransferAcrossShards,
+    /// An administrative action while the processor isn't in the admin-input mode
+    AdminInputDisabled,
+    /// A record replayed from the journal doesn't result in the states recorded along with it
+    JournalMismatch,
+    /// A shard of the `ShardedProcessor` doesn't accept records after a fatal error
+    ShardStopped,
+}
+
+impl Error {
+    /// The stable machine-readable code of the error
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::AmountUnspecified => "processor.amount_unspecified",
+            Self::AmountUnnecessary => "processor.amount_unnecessary",
+            Self::AmountNegative => "processor.amount_negative",
+            Self::TransactionIdDuplicate => "processor.transaction_id_duplicate",
+            Self::TransactionNotFound => "processor.transaction_not_found",
+            Self::ClientIdMismatch => "processor.client_id_mismatch",
+            Self::ClientNotFound => "processor.client_not_found",
+            Self::RecipientUnspecified => "processor.recipient_unspecified",
+            Self::RecipientUnnecessary => "processor.recipient_unnecessary",
+            Self::CurrencyMismatch => "processor.currency_mismatch",
+            Self::TransferToSelf => "processor.transfer_to_self",
+            Self::TransferAcrossShards => "processor.transfer_across_shards",
+            Self::AdminInputDisabled => "processor.admin_input_disabled",
+            Self::JournalMismatch => "processor.journal_mismatch",
+            Self::ShardStopped => "processor.shard_stopped",
+        }
+    }
 }
 
-pub struct Processor<CS: Store<u16, Client>, TS: Store<u32, Transaction>> {
+/// How the processor treats a Dispute of a Withdrawal, with the `clap` feature it's an argument
+/// of the binaries as is
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "clap", derive(clap::ArgEnum))]
+pub enum WithdrawalDisputes {
+    /// Such a Dispute is rejected with `DisputeWithdrawal`
+    #[default]
+    Reject,
+    /// The withdrawn amount is credited to `held` on Dispute, then it's either released to
+    /// `available` on Chargeback or dropped on Resolve
+    Reverse,
+}
+
+/// How the processor treats a Dispute for more than the `available` funds of the client
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NegativeBalances {
+    /// Such a Dispute is rejected with `DisputeInsufficientFunds`
+    #[default]
+    Reject,
+    /// The `available` funds go negative, and the accounts with negative balances are flagged
+    /// for collections in the output
+    Allow,
+}
+
+pub struct Processor<CS: Store<ClientId, Client>, TS: Store<TransactionId, Transaction>> {
     client_store: CS,
     transaction_store: TS,
+    withdrawal_disputes: WithdrawalDisputes,
+    negative_balances: NegativeBalances,
+    default_currency: Currency,
+    currency_column: bool,
+    admin_input: bool,
+    journal: Option<Journal>,
+    ledger: Option<Ledger>,
+    /// The sequence number of the next entry of the journal
+    journal_sequence: u64,
+    stats: ProcessingStats,
+    /// When the first and the last record have been processed
+    started: Option<Instant>,
+    finished: Option<Instant>,
 }
 
-impl<CS: Store<u16, Client>, TS: Store<u32, Transaction>> Processor<CS, TS> {
+impl<CS: Store<ClientId, Client>, TS: Store<TransactionId, Transaction>> Processor<CS, TS> {
     pub fn new(client_store: CS, transaction_store: TS) -> Self {
         Self {
             client_store,
             transaction_store,
+            withdrawal_disputes: WithdrawalDisputes::default(),
+            negative_balances: NegativeBalances::default(),
+            default_currency: Currency::default(),
+            currency_column: false,
+            admin_input: false,
+            journal: None,
+            ledger: None,
+            journal_sequence: 0,
+            stats: ProcessingStats::default(),
+            started: None,
+            finished: None,
+        }
+    }
+
+    /// Optional policy on the disputes of withdrawals, `WithdrawalDisputes::Reject` by default
+    #[must_use]
+    pub fn set_withdrawal_disputes(self, withdrawal_disputes: WithdrawalDisputes) -> Self {
+        Self {
+            withdrawal_disputes,
+            ..self
+        }
+    }
+
+    /// Optional policy on the disputes for more than the available funds,
+    /// `NegativeBalances::Reject` by default
+    #[must_use]
+    pub fn set_negative_balances(self, negative_balances: NegativeBalances) -> Self {
+        Self {
+            negative_balances,
+            ..self
+        }
+    }
+
+    /// Optional currency of the records that don't specify one, `USD` by default
+    #[must_use]
+    pub fn set_default_currency(self, default_currency: Currency) -> Self {
+        Self {
+            default_currency,
+            ..self
+        }
+    }
+
+    /// Optional currency column of the output, which tells apart the accounts of a client
+    /// in several currencies, disabled by default
+    #[must_use]
+    pub fn set_currency_column(self, currency_column: bool) -> Self {
+        Self {
+            currency_column,
+            ..self
+        }
+    }
+
+    /// Optional admin-input mode that accepts the administrative actions, disabled by default
+    #[must_use]
+    pub fn set_admin_input(self, admin_input: bool) -> Self {
+        Self {
+            admin_input,
+            ..self
+        }
+    }
+
+    /// Optional journal that every accepted record is appended to along with the resulting states
+    /// of the transaction and the clients
+    ///
+    /// # Errors
+    /// The journal may have entries already, they're read to continue the sequence
+    pub fn set_journal(self, journal: Journal) -> Result<Self, EngineError> {
+        let mut journal_sequence = 0;
+        for sequence in journal.keys() {
+            journal_sequence = journal_sequence.max(sequence?.0 + 1);
+        }
+        Ok(Self {
+            journal: Some(journal),
+            journal_sequence,
+            ..self
+        })
+    }
+
+    /// Optional ledger that every accepted record is posted to
+    #[must_use]
+    pub fn set_ledger(self, ledger: Ledger) -> Self {
+        Self {
+            ledger: Some(ledger),
+            ..self
+        }
+    }
+
+    #[must_use]
+    pub fn ledger(&self) -> Option<&Ledger> {
+        self.ledger.as_ref()
+    }
+
+    /// Checks the trial balance of the ledger and reconciles it against every client,
+    /// a no-op without the ledger
+    ///
+    /// # Errors
+    pub fn reconcile_ledger(&self) -> Result<(), EngineError> {
+        if let Some(ledger) = &self.ledger {
+            ledger.trial_balance()?;
+            for pair in self.client_store.iter() {
+                let (_, client) = pair?;
+                ledger.reconcile(&client)?;
+            }
         }
+        Ok(())
     }
 
     /// # Errors
-    pub fn process(&mut self, record: &Record) -> Result<(), Box<dyn StdError>> {
+    pub fn process(&mut self, record: &Record) -> Result<(), EngineError> {
+        self.started.get_or_insert_with(Instant::now);
+        let result = self.process_record(record);
+        self.finished = Some(Instant::now());
+        result.map_err(|error| self.count_rejected(error))
+    }
+
+    /// The summary of the records processed so far along with the number of the locked accounts
+    ///
+    /// # Errors
+    /// The clients are read from the store to count the locked ones
+    pub fn stats(&self) -> Result<ProcessingStats, EngineError> {
+        let mut stats = self.stats.clone();
+        for pair in self.client_store.iter() {
+            let (_, client) = pair?;
+            if client.locked() {
+                stats.locked_accounts += 1;
+            }
+        }
+        if let (Some(started), Some(finished)) = (self.started, self.finished) {
+            stats.set_duration(finished - started);
+        }
+        Ok(stats)
+    }
+
+    /// Counts a rejection of a record, including the ones rejected before reaching `process`
+    pub(crate) fn count_rejected(&mut self, error: EngineError) -> EngineError {
+        self.stats.reject(error.code());
+        error
+    }
+
+    fn process_record(&mut self, record: &Record) -> Result<(), EngineError> {
+        if record.recipient_id.is_some() && !matches!(record.action, Action::Transfer) {
+            return Err(EngineError::Processor(Error::RecipientUnnecessary));
+        }
+
         if let Action::Deposit | Action::Withdrawal = record.action {
             self.process_init(record)?;
+        } else if let Action::Transfer = record.action {
+            self.process_transfer(record)?;
         } else if let Action::Dispute | Action::Resolve | Action::ChargeBack = record.action {
             self.process_mut(record)?;
+        } else if record.action.is_admin() {
+            self.process_admin(record)?;
         }
 
         Ok(())
     }
 
+    /// The clients sorted by ID and currency, i.e. identical inputs produce identical outputs
+    ///
     /// # Errors
-    pub fn clients_csv(
-        &mut self,
-    ) -> Result<Box<dyn Iterator<Item = ClientCSV> + '_>, Box<dyn StdError>> {
-        Ok(Box::new(self.client_store.keys()?.into_iter().map(|id| {
-            self.client_store
-                .get(&id)
-                .expect("Store should be accessible")
-                .expect("Client should exist")
-                .into()
+    /// # Panics
+    /// See `clients_csv_unsorted`
+    pub fn clients_csv(&self) -> Result<Box<dyn Iterator<Item = ClientCSV> + '_>, EngineError> {
+        Ok(sorted(self.clients_csv_unsorted()?))
+    }
+
+    /// The clients are streamed from the store in no particular order without affecting
+    /// the in-memory part of the store
+    ///
+    /// # Errors
+    /// # Panics
+    /// The iterator panics if the store fails while the clients are being read
+    pub fn clients_csv_unsorted(
+        &self,
+    ) -> Result<Box<dyn Iterator<Item = ClientCSV> + '_>, EngineError> {
+        Ok(Box::new(self.client_store.iter().flat_map(move |pair| {
+            let (_, client) = pair.expect("Store should be accessible");
+            self.rows(&client)
         })))
     }
 
-    fn process_init(&mut self, record: &Record) -> Result<(), Box<dyn StdError>> {
-        if let Some(amount) = record.amount {
-            if amount >= 0.0 {
-                if self
-                    .transaction_store
-                    .get(&record.transaction_id)?
-                    .is_none()
+    /// The rows of a single client ordered by currency, none when the client is unknown
+    ///
+    /// # Errors
+    pub fn client_csv(&mut self, id: ClientId) -> Result<Vec<ClientCSV>, EngineError> {
+        Ok(self
+            .client(id)?
+            .map(|client| self.rows(&client))
+            .unwrap_or_default())
+    }
+
+    /// # Errors
+    pub fn transaction(&mut self, id: TransactionId) -> Result<Option<Transaction>, EngineError> {
+        Ok(self.transaction_store.get(&id)?.cloned())
+    }
+
+    /// Makes sure all the modifications reach the stores, e.g. to checkpoint at the boundary
+    /// of a batch of records
+    ///
+    /// # Errors
+    pub fn flush(&mut self) -> Result<(), EngineError> {
+        self.client_store.flush()?;
+        self.transaction_store.flush()?;
+        if let Some(journal) = &mut self.journal {
+            journal.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Stops journaling and hands the journal back, e.g. to replay it without reopening
+    /// the underlying database
+    #[must_use]
+    pub fn into_journal(self) -> Option<Journal> {
+        self.journal
+    }
+
+    /// Rebuilds the stores from scratch by processing the records of the journal once again,
+    /// and verifies that every record results in the very states recorded along with it.
+    /// The processor is expected to be new and configured the same way as the one that has
+    /// written the journal
+    ///
+    /// # Errors
+    /// `JournalMismatch` when the states differ, or the error of a record that is rejected
+    /// although it has been accepted originally
+    pub fn replay(
+        &mut self,
+        journal: &mut dyn Store<Sequence, JournalEntry>,
+    ) -> Result<(), EngineError> {
+        for entry in entries(journal) {
+            let (_, entry) = entry?;
+            self.process(&entry.record)?;
+
+            if let Some(transaction) = &entry.transaction {
+                if self.transaction_store.get(&transaction.id())? != Some(transaction) {
+                    return Err(EngineError::Processor(Error::JournalMismatch));
+                }
+            }
+            for state in &entry.clients {
+                let currency = state.balance.as_ref().map(|(currency, _)| currency);
+                let replayed = self.client_store.get(&state.id)?;
+                if replayed
+                    .map(|client| ClientState::new(client, currency))
+                    .as_ref()
+                    != Some(state)
                 {
-                    let mut client =
-                        if let Some(client) = self.client_store.get(&record.client_id)? {
-                            client.clone()
-                        } else {
-                            Client::new(record.client_id)
-                        };
-
-                    let transaction_action = if let Action::Withdrawal = record.action {
-                        client.withdraw(amount)?;
-                        TransactionAction::Withdrawal
-                    } else if let Action::Deposit = record.action {
-                        client.deposit(amount)?;
-                        TransactionAction::Deposit
-                    } else {
-                        unreachable!();
-                    };
+                    return Err(EngineError::Processor(Error::JournalMismatch));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn transaction_ids(
+        &self,
+    ) -> impl Iterator<Item = Result<TransactionId, EngineError>> + '_ {
+        self.transaction_store
+            .keys()
+            .map(|id| id.map_err(EngineError::from))
+    }
+
+    fn process_init(&mut self, record: &Record) -> Result<(), EngineError> {
+        let amount = required_amount(record)?;
+        if self
+            .transaction_store
+            .get(&record.transaction_id)?
+            .is_none()
+        {
+            let mut client = self.client_or_new(record.client_id)?;
+            let currency = self.currency(record);
+
+            let transaction_action = if let Action::Withdrawal = record.action {
+                client.withdraw(&currency, amount)?;
+                TransactionAction::Withdrawal
+            } else if let Action::Deposit = record.action {
+                client.deposit(&currency, amount)?;
+                TransactionAction::Deposit
+            } else {
+                unreachable!();
+            };
+
+            self.commit(
+                record,
+                Some(Transaction::new(
+                    record.transaction_id,
+                    client.id(),
+                    currency,
+                    amount,
+                    transaction_action,
+                )),
+                amount,
+                vec![client],
+            )
+        } else {
+            Err(EngineError::Processor(Error::TransactionIdDuplicate))
+        }
+    }
+
+    fn process_transfer(&mut self, record: &Record) -> Result<(), EngineError> {
+        let amount = required_amount(record)?;
+        if let Some(recipient_id) = record.recipient_id {
+            if recipient_id == record.client_id {
+                Err(EngineError::Processor(Error::TransferToSelf))
+            } else if self
+                .transaction_store
+                .get(&record.transaction_id)?
+                .is_none()
+            {
+                let mut sender = self.client_or_new(record.client_id)?;
+                let mut recipient = self.client_or_new(recipient_id)?;
+                let currency = self.currency(record);
 
-                    self.transaction_store.insert(
+                sender.withdraw(&currency, amount)?;
+                recipient.deposit(&currency, amount)?;
+
+                self.commit(
+                    record,
+                    Some(Transaction::new_transfer(
                         record.transaction_id,
-                        Transaction::new(
-                            record.transaction_id,
-                            client.id(),
-                            amount,
-                            transaction_action,
-                        ),
-                    )?;
-                    self.client_store.insert(client.id(), client)?;
-
-                    Ok(())
-                } else {
-                    Err(Box::new(Error::TransactionIdDuplicate))
-                }
+                        sender.id(),
+                        recipient.id(),
+                        currency,
+                        amount,
+                    )),
+                    amount,
+                    vec![sender, recipient],
+                )
             } else {
-                Err(Box::new(Error::AmountNegative))
+                Err(EngineError::Processor(Error::TransactionIdDuplicate))
             }
         } else {
-            Err(Box::new(Error::AmountUnspecified))
+            Err(EngineError::Processor(Error::RecipientUnspecified))
         }
     }
 
-    fn process_mut(&mut self, record: &Record) -> Result<(), Box<dyn StdError>> {
-        if record.amount.is_none() {
-            if let Some(transaction) = self.transaction_store.get(&record.transaction_id)? {
-                if transaction.client_id() == record.client_id {
-                    if let Some(client) = self.client_store.get(&record.client_id)? {
-                        let mut client = client.clone();
-                        let mut transaction = transaction.clone();
+    /// The funds of a Deposit are held at (and charged back from) the client who has made it,
+    /// while the funds of a Transfer are held at the recipient and charged back to the sender.
+    ///
+    /// The amount is optional, a record without it covers the whole transaction on Dispute and
+    /// all the open dispute slices on Resolve or Chargeback. So is the currency, the funds are
+    /// always held in the currency of the transaction
+    fn process_mut(&mut self, record: &Record) -> Result<(), EngineError> {
+        if record.amount.is_some_and(Amount::is_negative) {
+            return Err(EngineError::Processor(Error::AmountNegative));
+        }
+
+        if let Some(transaction) = self.transaction_store.get(&record.transaction_id)? {
+            if transaction.client_id() != record.client_id {
+                Err(EngineError::Processor(Error::ClientIdMismatch))
+            } else if record
+                .currency
+                .as_ref()
+                .is_some_and(|currency| currency != transaction.currency())
+            {
+                Err(EngineError::Processor(Error::CurrencyMismatch))
+            } else {
+                let mut transaction = transaction.clone();
+                let currency = transaction.currency().clone();
+                if let Some(client) = self.client(record.client_id)? {
+                    let mut clients = vec![];
 
+                    let mut holder = if let Some(recipient_id) = transaction.recipient_id() {
+                        clients.push(client);
+                        self.client(recipient_id)?
+                            .ok_or(EngineError::Processor(Error::ClientNotFound))?
+                    } else {
+                        client
+                    };
+
+                    // a withdrawal that can't be disputed is never in dispute, hence its resolves
+                    // and chargebacks are rejected by the transaction as usual
+                    let withdrawal = transaction.action() == TransactionAction::Withdrawal;
+                    if withdrawal
+                        && self.withdrawal_disputes == WithdrawalDisputes::Reject
+                        && matches!(record.action, Action::Dispute)
+                    {
+                        return Err(TransactionError::DisputeWithdrawal.into());
+                    }
+
+                    let amount = if let Action::Dispute = record.action {
+                        transaction.dispute(record.amount)?
+                    } else if let Action::Resolve = record.action {
+                        transaction.resolve(record.amount)?
+                    } else if let Action::ChargeBack = record.action {
+                        transaction.chargeback(record.amount)?
+                    } else {
+                        unreachable!();
+                    };
+
+                    if withdrawal {
                         if let Action::Dispute = record.action {
-                            transaction.dispute()?;
-                            client.dispute(transaction.amount())?;
+                            holder.dispute_withdrawal(&currency, amount)?;
                         } else if let Action::Resolve = record.action {
-                            transaction.resolve()?;
-                            client.resolve(transaction.amount())?;
+                            holder.resolve_withdrawal(&currency, amount)?;
                         } else if let Action::ChargeBack = record.action {
-                            transaction.chargeback()?;
-                            client.chargeback(transaction.amount())?;
+                            holder.chargeback_withdrawal(&currency, amount)?;
                         }
+                    } else if let Action::Dispute = record.action {
+                        if let NegativeBalances::Allow = self.negative_balances {
+                            holder.dispute_overdrawn(&currency, amount)?;
+                        } else {
+                            holder.dispute(&currency, amount)?;
+                        }
+                    } else if let Action::Resolve = record.action {
+                        holder.resolve(&currency, amount)?;
+                    } else if let Action::ChargeBack = record.action {
+                        holder.chargeback(&currency, amount)?;
+                        if let Some(sender) = clients.first_mut() {
+                            sender.refund(&currency, amount)?;
+                        }
+                    }
 
-                        self.transaction_store
-                            .insert(transaction.id(), transaction)?;
-                        self.client_store.insert(client.id(), client)?;
+                    clients.push(holder);
+                    self.commit(record, Some(transaction), amount, clients)
+                } else {
+                    Err(EngineError::Processor(Error::ClientNotFound))
+                }
+            }
+        } else {
+            Err(EngineError::Processor(Error::TransactionNotFound))
+        }
+    }
 
-                        Ok(())
-                    } else {
-                        Err(Box::new(Error::ClientNotFound))
-                    }
+    /// The ID of an administrative action is only recorded in the transitions of the client,
+    /// it isn't a transaction ID
+    fn process_admin(&mut self, record: &Record) -> Result<(), EngineError> {
+        if !self.admin_input {
+            Err(EngineError::Processor(Error::AdminInputDisabled))
+        } else if record.amount.is_some() {
+            Err(EngineError::Processor(Error::AmountUnnecessary))
+        } else if let Some(mut client) = self.client(record.client_id)? {
+            if let Action::Unlock = record.action {
+                client.unlock(record.transaction_id)?;
+            } else if let Action::Close = record.action {
+                client.close(record.transaction_id)?;
+            }
+            self.commit(record, None, Amount::ZERO, vec![client])
+        } else {
+            Err(EngineError::Processor(Error::ClientNotFound))
+        }
+    }
+
+    fn currency(&self, record: &Record) -> Currency {
+        record
+            .currency
+            .clone()
+            .unwrap_or_else(|| self.default_currency.clone())
+    }
+
+    fn rows(&self, client: &Client) -> Vec<ClientCSV> {
+        let collections = self.negative_balances == NegativeBalances::Allow;
+        client
+            .clients_csv()
+            .map(|client_csv| {
+                let client_csv = if self.currency_column {
+                    client_csv.with_currency()
                 } else {
-                    Err(Box::new(Error::ClientIdMismatch))
+                    client_csv
+                };
+                let client_csv = if collections {
+                    client_csv.with_collections()
+                } else {
+                    client_csv
+                };
+                if self.admin_input {
+                    client_csv.with_status(client.status())
+                } else {
+                    client_csv
                 }
-            } else {
-                Err(Box::new(Error::TransactionNotFound))
+            })
+            .collect()
+    }
+
+    fn client(&mut self, id: ClientId) -> Result<Option<Client>, EngineError> {
+        Ok(self.client_store.get(&id)?.cloned())
+    }
+
+    fn client_or_new(&mut self, id: ClientId) -> Result<Client, EngineError> {
+        Ok(self.client(id)?.unwrap_or_else(|| Client::new(id)))
+    }
+
+    /// All the validation is done on the copies of the transaction and the clients
+    /// by the time they're written, so a record is either applied fully or not at all.
+    /// The ledger is the last one to validate the record, then the journal is written ahead
+    /// of the stores. `amount` is what the record has actually moved
+    fn commit(
+        &mut self,
+        record: &Record,
+        transaction: Option<Transaction>,
+        amount: Amount,
+        clients: Vec<Client>,
+    ) -> Result<(), EngineError> {
+        if let (Some(ledger), Some(transaction)) = (&mut self.ledger, &transaction) {
+            if let Some(posting) = posting(&record.action, transaction, amount) {
+                ledger.post(posting)?;
             }
+        }
+
+        let currency = transaction
+            .as_ref()
+            .map(|transaction| transaction.currency().clone());
+        if let Some(journal) = &mut self.journal {
+            journal.insert(
+                Sequence(self.journal_sequence),
+                JournalEntry {
+                    record: record.clone(),
+                    transaction: transaction.clone(),
+                    clients: clients
+                        .iter()
+                        .map(|client| ClientState::new(client, currency.as_ref()))
+                        .collect(),
+                },
+            )?;
+            self.journal_sequence += 1;
+        }
+
+        if let Some(transaction) = transaction {
+            self.transaction_store
+                .insert(transaction.id(), transaction)?;
+        }
+        for client in clients {
+            self.client_store.insert(client.id(), client)?;
+        }
+
+        self.stats.accept(&record.action, currency.as_ref(), amount);
+        Ok(())
+    }
+}
+
+fn required_amount(record: &Record) -> Result<Amount, EngineError> {
+    if let Some(amount) = record.amount {
+        if amount >= Amount::ZERO {
+            Ok(amount)
         } else {
-            Err(Box::new(Error::AmountUnnecessary))
+            Err(EngineError::Processor(Error::AmountNegative))
         }
+    } else {
+        Err(EngineError::Processor(Error::AmountUnspecified))
     }
 }
+
+pub(crate) fn sorted<'a>(
+    clients: impl Iterator<Item = ClientCSV> + 'a,
+) -> Box<dyn Iterator<Item = ClientCSV> + 'a> {
+    let mut clients = clients.collect::<Vec<_>>();
+    clients.sort_unstable_by(|a, b| (a.id, &a.currency).cmp(&(b.id, &b.currency)));
+    Box::new(clients.into_iter())
+}
@@ -0,0 +1,282 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        mpsc::{sync_channel, SyncSender},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+};
+
+use crate::{
+    client::{Client, ClientCSV, ClientId},
+    error::Error as EngineError,
+    input::{Action, Record},
+    processor::{sorted, Error, Processor},
+    stats::ProcessingStats,
+    transaction::{Transaction, TransactionId},
+};
+use store::store::Store;
+
+/// The number of records that may wait in the queue of a single shard
+const QUEUE_SIZE: usize = 1024;
+
+type ErrorHandler<S> = Arc<dyn Fn(&Record, &S, &EngineError) + Send + Sync>;
+type RegistryStore = Box<dyn Store<TransactionId, ()> + Send>;
+type Worker<CS, TS> = JoinHandle<Result<Processor<CS, TS>, EngineError>>;
+
+/// Runs N instances of `Processor` in N threads, every record is routed to the shard
+/// `record.client_id % N`, hence all the records of a client are processed in order.
+///
+/// The IDs of Deposit and Withdrawal transactions are claimed in a registry shared across the
+/// shards, so that an ID accepted by one shard is rejected as a duplicate by all the others.
+/// A shard that meets an ID being processed by another shard waits for the outcome. The accepted
+/// IDs are kept in a store of their own, e.g. a `StoreDB` to bound the memory they take.
+///
+/// A Transfer between two clients that belong to different shards is rejected with
+/// `TransferAcrossShards`, since the shards can't update both clients atomically.
+///
+/// Every record is queued along with its source `S`, e.g. the row of the input it's been parsed
+/// from, which is handed back to `on_error` when the record is rejected
+pub struct ShardedProcessor<CS, TS, S = ()>
+where
+    CS: Store<ClientId, Client> + Send + 'static,
+    TS: Store<TransactionId, Transaction> + Send + 'static,
+    S: Send + 'static,
+{
+    senders: Vec<SyncSender<(Record, S)>>,
+    workers: Vec<Worker<CS, TS>>,
+    processors: Vec<Processor<CS, TS>>,
+}
+
+impl<CS, TS, S> ShardedProcessor<CS, TS, S>
+where
+    CS: Store<ClientId, Client> + Send + 'static,
+    TS: Store<TransactionId, Transaction> + Send + 'static,
+    S: Send + 'static,
+{
+    /// Spawns a worker thread per each of the `processors`, all the records rejected by the
+    /// workers are reported to the `on_error` callback from within the worker threads.
+    /// `registry` is the store of the transaction IDs accepted by all the shards
+    ///
+    /// # Errors
+    /// The IDs of the transactions already known to the `processors` are copied from their stores
+    /// to the `registry`
+    ///
+    /// # Panics
+    /// When `processors` is empty
+    pub fn new<F>(
+        processors: Vec<Processor<CS, TS>>,
+        registry: impl Store<TransactionId, ()> + Send + 'static,
+        on_error: F,
+    ) -> Result<Self, EngineError>
+    where
+        F: Fn(&Record, &S, &EngineError) + Send + Sync + 'static,
+    {
+        assert!(!processors.is_empty(), "At least one shard is required");
+
+        let registry = Registry::new(Box::new(registry));
+        for processor in &processors {
+            registry.accept(processor.transaction_ids())?;
+        }
+        let registry = Arc::new(registry);
+        let on_error: ErrorHandler<S> = Arc::new(on_error);
+
+        let shards = processors.len();
+        let (senders, workers) = processors
+            .into_iter()
+            .map(|processor| {
+                let (sender, receiver) = sync_channel::<(Record, S)>(QUEUE_SIZE);
+                let registry = Arc::clone(&registry);
+                let on_error = Arc::clone(&on_error);
+                let worker = thread::spawn(move || {
+                    let mut processor = processor;
+                    for (record, source) in receiver {
+                        if let Err(error) =
+                            process_claimed(&mut processor, &registry, shards, &record)
+                        {
+                            on_error(&record, &source, &error);
+                            if error.is_fatal() {
+                                return Err(error);
+                            }
+                        }
+                    }
+                    Ok(processor)
+                });
+                (sender, worker)
+            })
+            .unzip();
+
+        Ok(Self {
+            senders,
+            workers,
+            processors: vec![],
+        })
+    }
+
+    /// Queues the record to the shard of its client, the outcome of the processing is only
+    /// reported to `on_error` in case of a rejection
+    ///
+    /// # Errors
+    /// `ShardStopped` when the shard has stopped after a fatal error or has been joined already
+    pub fn process(&self, record: Record, source: S) -> Result<(), EngineError> {
+        self.senders
+            .get(shard_of(record.client_id, self.senders.len()))
+            .ok_or(Error::ShardStopped)?
+            .send((record, source))
+            .map_err(|_| Error::ShardStopped.into())
+    }
+
+    /// Waits for all the queued records to be processed
+    ///
+    /// # Errors
+    /// The first fatal error that any of the shards has stopped with
+    ///
+    /// # Panics
+    /// When a worker thread panics
+    pub fn join(&mut self) -> Result<(), EngineError> {
+        self.senders.clear();
+        let mut result = Ok(());
+        for worker in self.workers.drain(..) {
+            match worker.join().expect("Worker thread joined") {
+                Ok(processor) => self.processors.push(processor),
+                Err(error) => result = result.and(Err(error)),
+            }
+        }
+        result
+    }
+
+    /// Joins the shards and merges their accounts sorted by the client ID
+    ///
+    /// # Errors
+    /// See `join` and `Processor::clients_csv`
+    pub fn clients_csv(&mut self) -> Result<Box<dyn Iterator<Item = ClientCSV> + '_>, EngineError> {
+        Ok(sorted(self.clients_csv_unsorted()?))
+    }
+
+    /// Joins the shards and chains their accounts in no particular order
+    ///
+    /// # Errors
+    /// See `join` and `Processor::clients_csv_unsorted`
+    pub fn clients_csv_unsorted(
+        &mut self,
+    ) -> Result<Box<dyn Iterator<Item = ClientCSV> + '_>, EngineError> {
+        self.join()?;
+        let mut shards = vec![];
+        for processor in &self.processors {
+            shards.push(processor.clients_csv_unsorted()?);
+        }
+        Ok(Box::new(shards.into_iter().flatten()))
+    }
+
+    /// Joins the shards and merges their stats
+    ///
+    /// # Errors
+    /// See `join` and `Processor::stats`
+    pub fn stats(&mut self) -> Result<ProcessingStats, EngineError> {
+        self.join()?;
+        let mut stats = ProcessingStats::default();
+        for processor in &self.processors {
+            stats.merge(&processor.stats()?);
+        }
+        Ok(stats)
+    }
+}
+
+/// The transaction IDs being processed, at most one per shard, and the accepted ones
+struct Ids {
+    pending: HashSet<TransactionId>,
+    accepted: RegistryStore,
+}
+
+struct Registry {
+    ids: Mutex<Ids>,
+    resolved: Condvar,
+}
+
+impl Registry {
+    fn new(accepted: RegistryStore) -> Self {
+        Self {
+            ids: Mutex::new(Ids {
+                pending: HashSet::new(),
+                accepted,
+            }),
+            resolved: Condvar::new(),
+        }
+    }
+
+    fn accept(
+        &self,
+        ids: impl Iterator<Item = Result<TransactionId, EngineError>>,
+    ) -> Result<(), EngineError> {
+        let mut registry = self.lock();
+        for id in ids {
+            registry.accepted.insert(id?, ())?;
+        }
+        Ok(())
+    }
+
+    /// Returns `false` when the ID has been accepted already
+    fn claim(&self, id: TransactionId) -> Result<bool, EngineError> {
+        let mut ids = self.lock();
+        while ids.pending.contains(&id) {
+            ids = self.resolved.wait(ids).expect("Registry isn't poisoned");
+        }
+        if ids.accepted.get(&id)?.is_some() {
+            Ok(false)
+        } else {
+            ids.pending.insert(id);
+            Ok(true)
+        }
+    }
+
+    /// The waiting shards are woken up even if the accepted ID fails to be stored
+    fn resolve(&self, id: TransactionId, accepted: bool) -> Result<(), EngineError> {
+        let mut ids = self.lock();
+        let result = if accepted {
+            ids.accepted.insert(id, ()).map(|_| ())
+        } else {
+            Ok(())
+        };
+        ids.pending.remove(&id);
+        self.resolved.notify_all();
+        Ok(result?)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Ids> {
+        self.ids.lock().expect("Registry isn't poisoned")
+    }
+}
+
+fn shard_of(client_id: ClientId, shards: usize) -> usize {
+    // the remainder is less than the number of shards, so it always fits back into usize
+    let shards = u64::try_from(shards.max(1)).unwrap_or(u64::MAX);
+    usize::try_from(client_id % shards).unwrap_or_default()
+}
+
+fn process_claimed<CS, TS>(
+    processor: &mut Processor<CS, TS>,
+    registry: &Registry,
+    shards: usize,
+    record: &Record,
+) -> Result<(), EngineError>
+where
+    CS: Store<ClientId, Client>,
+    TS: Store<TransactionId, Transaction>,
+{
+    if let (Action::Transfer, Some(recipient_id)) = (&record.action, record.recipient_id) {
+        if shard_of(recipient_id, shards) != shard_of(record.client_id, shards) {
+            return Err(processor.count_rejected(Error::TransferAcrossShards.into()));
+        }
+    }
+
+    if let Action::Deposit | Action::Withdrawal | Action::Transfer = record.action {
+        if !registry.claim(record.transaction_id)? {
+            return Err(processor.count_rejected(Error::TransactionIdDuplicate.into()));
+        }
+        let result = processor.process(record);
+        registry.resolve(record.transaction_id, result.is_ok())?;
+        result
+    } else {
+        processor.process(record)
+    }
+}
@@ -0,0 +1,153 @@
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use crate::{amount::Amount, currency::Currency, input::Action};
+
+/// The summary of a run, see `Processor::stats`
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ProcessingStats {
+    /// The number of records given to the processor, plus the rows that couldn't be parsed
+    /// into a record when they're added by `add_rejected`
+    pub read: u64,
+    pub accepted: u64,
+    /// The number of rejected records by the code of the error
+    pub rejected: BTreeMap<&'static str, u64>,
+    /// The amounts moved by the accepted records by currency
+    pub totals: BTreeMap<Currency, Totals>,
+    /// The accounts locked by a chargeback, whether they've been closed since or not,
+    /// i.e. the accounts written with `locked` set
+    pub locked_accounts: u64,
+    /// The time between the first and the last record processed
+    pub elapsed_seconds: f64,
+    pub records_per_second: f64,
+}
+
+/// The totals are informational, a total that overflows sticks to the bound of `Amount` rather
+/// than failing the run, and `overflowed` is set
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Totals {
+    pub deposited: Amount,
+    pub withdrawn: Amount,
+    pub transferred: Amount,
+    pub disputed: Amount,
+    pub resolved: Amount,
+    pub charged_back: Amount,
+    pub overflowed: bool,
+}
+
+impl ProcessingStats {
+    /// Counts `count` records rejected with the error `code`, e.g. the malformed rows of the input
+    pub fn add_rejected(&mut self, code: &'static str, count: u64) {
+        if count > 0 {
+            self.read += count;
+            *self.rejected.entry(code).or_default() += count;
+            self.set_elapsed(self.elapsed_seconds);
+        }
+    }
+
+    /// Adds up the stats of another processor, e.g. of another shard, the shards are expected
+    /// to run concurrently so the longest of them is the elapsed time of all of them
+    pub fn merge(&mut self, other: &Self) {
+        self.read += other.read;
+        self.accepted += other.accepted;
+        for (code, count) in &other.rejected {
+            *self.rejected.entry(code).or_default() += count;
+        }
+        for (currency, totals) in &other.totals {
+            self.totals.entry(currency.clone()).or_default().add(totals);
+        }
+        self.locked_accounts += other.locked_accounts;
+        self.set_elapsed(self.elapsed_seconds.max(other.elapsed_seconds));
+    }
+
+    /// The total number of rejected records
+    #[must_use]
+    pub fn rejected_total(&self) -> u64 {
+        self.rejected.values().sum()
+    }
+
+    pub(crate) fn reject(&mut self, code: &'static str) {
+        self.add_rejected(code, 1);
+    }
+
+    pub(crate) fn accept(&mut self, action: &Action, currency: Option<&Currency>, amount: Amount) {
+        self.read += 1;
+        self.accepted += 1;
+        if let Some(currency) = currency {
+            let totals = self.totals.entry(currency.clone()).or_default();
+            let total = match action {
+                Action::Deposit => &mut totals.deposited,
+                Action::Withdrawal => &mut totals.withdrawn,
+                Action::Transfer => &mut totals.transferred,
+                Action::Dispute => &mut totals.disputed,
+                Action::Resolve => &mut totals.resolved,
+                Action::ChargeBack => &mut totals.charged_back,
+                Action::Unlock | Action::Close => return,
+            };
+            saturating_add(total, amount, &mut totals.overflowed);
+        }
+    }
+
+    pub(crate) fn set_elapsed(&mut self, elapsed: f64) {
+        self.elapsed_seconds = elapsed;
+        #[allow(clippy::cast_precision_loss)]
+        let read = self.read as f64;
+        self.records_per_second = if elapsed > 0.0 { read / elapsed } else { 0.0 };
+    }
+
+    pub(crate) fn set_duration(&mut self, elapsed: Duration) {
+        self.set_elapsed(elapsed.as_secs_f64());
+    }
+}
+
+impl Totals {
+    fn add(&mut self, other: &Self) {
+        self.overflowed |= other.overflowed;
+        for (total, amount) in [
+            (&mut self.deposited, other.deposited),
+            (&mut self.withdrawn, other.withdrawn),
+            (&mut self.transferred, other.transferred),
+            (&mut self.disputed, other.disputed),
+            (&mut self.resolved, other.resolved),
+            (&mut self.charged_back, other.charged_back),
+        ] {
+            saturating_add(total, amount, &mut self.overflowed);
+        }
+    }
+}
+
+/// The summary for a human, one figure per line
+impl fmt::Display for ProcessingStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Records read: {}", self.read)?;
+        writeln!(f, "Accepted: {}", self.accepted)?;
+        writeln!(f, "Rejected: {}", self.rejected_total())?;
+        for (code, count) in &self.rejected {
+            writeln!(f, "  {code}: {count}")?;
+        }
+        for (currency, totals) in &self.totals {
+            writeln!(f, "Totals in {currency}:")?;
+            writeln!(f, "  deposited: {}", totals.deposited)?;
+            writeln!(f, "  withdrawn: {}", totals.withdrawn)?;
+            writeln!(f, "  transferred: {}", totals.transferred)?;
+            writeln!(f, "  disputed: {}", totals.disputed)?;
+            writeln!(f, "  resolved: {}", totals.resolved)?;
+            writeln!(f, "  charged back: {}", totals.charged_back)?;
+            if totals.overflowed {
+                writeln!(f, "  overflowed, the totals are capped")?;
+            }
+        }
+        writeln!(f, "Locked accounts: {}", self.locked_accounts)?;
+        write!(
+            f,
+            "Throughput: {:.0} records/s ({:.3}s)",
+            self.records_per_second, self.elapsed_seconds
+        )
+    }
+}
+
+fn saturating_add(total: &mut Amount, amount: Amount, overflowed: &mut bool) {
+    let sum = total.scaled().saturating_add(amount.scaled());
+    *overflowed |= total.checked_add(amount).is_none();
+    *total = Amount::from_scaled(sum);
+}
@@ -1,8 +1,12 @@
 use serde::{Deserialize, Serialize};
 
+use crate::{amount::Amount, client::ClientId, currency::Currency};
+
+pub type TransactionId = u64;
+
 #[derive(Debug, Error)]
 pub enum Error {
-    /// An attemt to dispute a withdrawal transaction
+    /// An attempt to dispute a withdrawal transaction while the processor rejects such disputes
     DisputeWithdrawal,
     /// An attempt to resolve a transaction that hasn't been disputed
     ResolveNonDisputed,
@@ -12,49 +16,121 @@ pub enum Error {
     AlreadyInDispute,
     /// An attempt to dispute a transaction that has already been charged back
     AlreadyChargedBack,
+    /// An attempt to dispute more than what's left undisputed of the transaction
+    AmountExceedsUndisputed,
+    /// An attempt to resolve or charge back more than what's in dispute
+    AmountExceedsDisputed,
+    /// An attempt to resolve or charge back an amount that isn't one of the open dispute slices
+    AmountMatchesNoSlice,
+    /// A dispute, resolve or chargeback of a zero amount
+    AmountZero,
+    /// The disputed or the charged back total doesn't fit into `Amount`
+    AmountOverflow,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+impl Error {
+    /// The stable machine-readable code of the error
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::DisputeWithdrawal => "transaction.dispute_withdrawal",
+            Self::ResolveNonDisputed => "transaction.resolve_non_disputed",
+            Self::ChargeBackNonDisputed => "transaction.charge_back_non_disputed",
+            Self::AlreadyInDispute => "transaction.already_in_dispute",
+            Self::AlreadyChargedBack => "transaction.already_charged_back",
+            Self::AmountExceedsUndisputed => "transaction.amount_exceeds_undisputed",
+            Self::AmountExceedsDisputed => "transaction.amount_exceeds_disputed",
+            Self::AmountMatchesNoSlice => "transaction.amount_matches_no_slice",
+            Self::AmountZero => "transaction.amount_zero",
+            Self::AmountOverflow => "transaction.amount_overflow",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Action {
     Deposit,
     Withdrawal,
+    Transfer,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Transaction {
-    id: u32,
-    client_id: u16,
-    amount: f32,
+    id: TransactionId,
+    client_id: ClientId,
+    /// The recipient of a Transfer, `client_id` is the sender
+    recipient_id: Option<ClientId>,
+    currency: Currency,
+    amount: Amount,
     action: Action,
-    in_dispute: bool,
-    charged_back: bool,
+    /// The sum of the open dispute slices
+    disputed: Amount,
+    /// The open dispute slices, oldest first
+    #[serde(with = "slices")]
+    slices: Vec<Amount>,
+    /// The sum of the slices that have been charged back
+    charged_back: Amount,
 }
 
 impl Transaction {
     #[must_use]
-    pub fn new(id: u32, client_id: u16, amount: f32, action: Action) -> Self {
+    pub fn new(
+        id: TransactionId,
+        client_id: ClientId,
+        currency: Currency,
+        amount: Amount,
+        action: Action,
+    ) -> Self {
         Self {
             id,
             client_id,
+            recipient_id: None,
+            currency,
             amount,
             action,
-            in_dispute: false,
-            charged_back: false,
+            disputed: Amount::ZERO,
+            slices: vec![],
+            charged_back: Amount::ZERO,
         }
     }
 
     #[must_use]
-    pub fn id(&self) -> u32 {
+    pub fn new_transfer(
+        id: TransactionId,
+        sender_id: ClientId,
+        recipient_id: ClientId,
+        currency: Currency,
+        amount: Amount,
+    ) -> Self {
+        Self {
+            recipient_id: Some(recipient_id),
+            ..Self::new(id, sender_id, currency, amount, Action::Transfer)
+        }
+    }
+
+    #[must_use]
+    pub fn id(&self) -> TransactionId {
         self.id
     }
 
     #[must_use]
-    pub fn client_id(&self) -> u16 {
+    pub fn client_id(&self) -> ClientId {
         self.client_id
     }
 
     #[must_use]
-    pub fn amount(&self) -> f32 {
+    pub fn recipient_id(&self) -> Option<ClientId> {
+        self.recipient_id
+    }
+
+    /// The disputes of the transaction apply to this currency
+    #[must_use]
+    pub fn currency(&self) -> &Currency {
+        &self.currency
+    }
+
+    #[must_use]
+    pub fn amount(&self) -> Amount {
         self.amount
     }
 
@@ -63,38 +139,127 @@ impl Transaction {
         self.action.clone()
     }
 
+    #[must_use]
+    pub fn disputed(&self) -> Amount {
+        self.disputed
+    }
+
+    #[must_use]
+    pub fn charged_back(&self) -> Amount {
+        self.charged_back
+    }
+
+    /// The amounts of the open dispute slices, oldest first
+    #[must_use]
+    pub fn slices(&self) -> &[Amount] {
+        &self.slices
+    }
+
+    /// Opens a dispute slice of `amount`, or of everything that's left undisputed when it's
+    /// `None`, and returns the amount of the slice.
+    ///
+    /// Whether a Withdrawal may be disputed at all is up to the policy of the processor,
+    /// see `processor::WithdrawalDisputes`
+    ///
     /// # Errors
-    pub fn dispute(&mut self) -> Result<(), Error> {
-        if let Action::Withdrawal = self.action {
-            Err(Error::DisputeWithdrawal)
-        } else if self.in_dispute {
+    pub fn dispute(&mut self, amount: Option<Amount>) -> Result<Amount, Error> {
+        if amount == Some(Amount::ZERO) {
+            return Err(Error::AmountZero);
+        }
+        // neither of the totals can exceed the amount of the transaction
+        let undisputed = self
+            .amount
+            .checked_sub(self.disputed)
+            .and_then(|undisputed| undisputed.checked_sub(self.charged_back))
+            .ok_or(Error::AmountOverflow)?;
+        let amount = amount.unwrap_or(undisputed);
+        if undisputed == Amount::ZERO && !self.slices.is_empty() {
             Err(Error::AlreadyInDispute)
-        } else if self.charged_back {
+        } else if undisputed == Amount::ZERO && self.charged_back > Amount::ZERO {
             Err(Error::AlreadyChargedBack)
+        } else if amount > undisputed {
+            Err(Error::AmountExceedsUndisputed)
         } else {
-            self.in_dispute = true;
-            Ok(())
+            self.disputed = add(self.disputed, amount)?;
+            self.slices.push(amount);
+            Ok(amount)
         }
     }
 
+    /// Closes the dispute slice of `amount`, or all of them when it's `None`,
+    /// and returns the amount released
+    ///
     /// # Errors
-    pub fn resolve(&mut self) -> Result<(), Error> {
-        if self.in_dispute {
-            self.in_dispute = false;
-            Ok(())
+    /// `AmountMatchesNoSlice` unless `amount` is the amount of one of the open slices
+    pub fn resolve(&mut self, amount: Option<Amount>) -> Result<Amount, Error> {
+        self.undispute(amount, Error::ResolveNonDisputed)
+    }
+
+    /// Same as `resolve`, but the amount released is recorded as charged back
+    ///
+    /// # Errors
+    pub fn chargeback(&mut self, amount: Option<Amount>) -> Result<Amount, Error> {
+        let amount = self.undispute(amount, Error::ChargeBackNonDisputed)?;
+        self.charged_back = add(self.charged_back, amount)?;
+        Ok(amount)
+    }
+
+    fn undispute(&mut self, amount: Option<Amount>, non_disputed: Error) -> Result<Amount, Error> {
+        match amount {
+            _ if self.slices.is_empty() => Err(non_disputed),
+            Some(Amount::ZERO) => Err(Error::AmountZero),
+            Some(amount) if amount > self.disputed => Err(Error::AmountExceedsDisputed),
+            Some(amount) => {
+                let slice = self
+                    .slices
+                    .iter()
+                    .position(|slice| *slice == amount)
+                    .ok_or(Error::AmountMatchesNoSlice)?;
+                self.disputed = self
+                    .disputed
+                    .checked_sub(amount)
+                    .ok_or(Error::AmountOverflow)?;
+                self.slices.remove(slice);
+                Ok(amount)
+            }
+            None => {
+                self.slices.clear();
+                Ok(std::mem::replace(&mut self.disputed, Amount::ZERO))
+            }
+        }
+    }
+}
+
+fn add(total: Amount, amount: Amount) -> Result<Amount, Error> {
+    total.checked_add(amount).ok_or(Error::AmountOverflow)
+}
+
+/// A CSV field can't hold a sequence, hence the human-readable formats get the slices as
+/// a space-separated string while the binary ones (bincode) get a sequence
+mod slices {
+    use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::amount::Amount;
+
+    pub fn serialize<S: Serializer>(slices: &[Amount], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let slices = slices.iter().map(ToString::to_string).collect::<Vec<_>>();
+            serializer.serialize_str(&slices.join(" "))
         } else {
-            Err(Error::ResolveNonDisputed)
+            slices.serialize(serializer)
         }
     }
 
-    /// # Errors
-    pub fn chargeback(&mut self) -> Result<(), Error> {
-        if self.in_dispute {
-            self.in_dispute = false;
-            self.charged_back = true;
-            Ok(())
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Amount>, D::Error> {
+        if deserializer.is_human_readable() {
+            String::deserialize(deserializer)?
+                .split_whitespace()
+                .map(|slice| slice.parse().map_err(D::Error::custom))
+                .collect()
         } else {
-            Err(Error::ChargeBackNonDisputed)
+            Vec::deserialize(deserializer)
         }
     }
 }
@@ -1,8 +1,12 @@
 use serde::Serialize;
 use std::error::Error;
-use std::io;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process;
 
 pub enum Output<'a> {
+    /// The file is replaced atomically once it's written completely
     File(&'a str),
     STDOUT,
 }
@@ -12,17 +16,57 @@ pub fn write_csv<T: Serialize, D: Iterator<Item = T>>(
     output: &Output<'_>,
     data: D,
 ) -> Result<(), Box<dyn Error>> {
-    if let Output::File(path) = output {
-        let mut writer = csv::Writer::from_path(path)?;
+    write_csv_headers(output, true, data)
+}
+
+/// Same as `write_csv`, the header row is omitted unless `headers` is set
+///
+/// # Errors
+pub fn write_csv_headers<T: Serialize, D: Iterator<Item = T>>(
+    output: &Output<'_>,
+    headers: bool,
+    data: D,
+) -> Result<(), Box<dyn Error>> {
+    write_output(output, |writer| {
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(headers)
+            .from_writer(writer);
         for record in data {
             writer.serialize(record)?;
         }
-    } else {
-        let mut writer = csv::Writer::from_writer(io::stdout());
-        for record in data {
-            writer.serialize(record)?;
+        Ok(writer.flush()?)
+    })
+}
+
+/// A file is written next to its path under a hidden temporary name and renamed once `write`
+/// succeeds, so that a reader of the path never sees a partial output
+pub(crate) fn write_output(
+    output: &Output<'_>,
+    write: impl FnOnce(&mut dyn Write) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    if let Output::File(path) = output {
+        let path = Path::new(path);
+        let tmp_path = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            process::id()
+        ));
+        let result = File::create(&tmp_path)
+            .map_err(Box::from)
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                write(&mut writer)?;
+                writer.flush()?;
+                Ok(writer.get_ref().sync_all()?)
+            })
+            .and_then(|()| Ok(fs::rename(&tmp_path, path)?));
+        if result.is_err() {
+            fs::remove_file(&tmp_path).ok();
         }
+        result
+    } else {
+        let mut writer = BufWriter::new(io::stdout().lock());
+        write(&mut writer)?;
+        Ok(writer.flush()?)
     }
-
-    Ok(())
 }
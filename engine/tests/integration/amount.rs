@@ -0,0 +1,88 @@
+use engine::amount::{Amount, Error};
+
+#[test]
+fn parse_and_format() {
+    for (input, output) in [
+        ("1", "1.0000"),
+        ("1.5", "1.5000"),
+        ("19.9999", "19.9999"),
+        (".25", "0.2500"),
+        ("-100.0", "-100.0000"),
+        ("-0.0001", "-0.0001"),
+        (" 2.0 ", "2.0000"),
+    ] {
+        let amount: Amount = input.parse().expect("Parsed");
+        assert_eq!(amount.to_string(), output, "Correct format of {}", input);
+    }
+}
+
+#[test]
+fn precision() {
+    for (input, precision, output) in [
+        ("1.5", 2, "1.50"),
+        ("1.005", 2, "1.01"),
+        ("1.0049", 2, "1.00"),
+        ("-1.005", 2, "-1.01"),
+        ("-0.0049", 2, "0.00"),
+        ("9.5", 0, "10"),
+        ("0.1234", 6, "0.123400"),
+        ("19.9999", 4, "19.9999"),
+    ] {
+        let amount: Amount = input.parse().expect("Parsed");
+        assert_eq!(
+            format!("{amount:.precision$}"),
+            output,
+            "Correct format of {input} to {precision} places"
+        );
+    }
+}
+
+#[test]
+fn rounding() {
+    for (input, places, output) in [
+        ("1.005", 2, Some(10_100)),
+        ("-1.005", 2, Some(-10_100)),
+        ("1.0049", 2, Some(10_000)),
+        ("9.5", 0, Some(100_000)),
+        ("0.1234", 6, Some(1_234)),
+    ] {
+        let amount: Amount = input.parse().expect("Parsed");
+        assert_eq!(
+            output.map(Amount::from_scaled),
+            amount.checked_round(places),
+            "Correct rounding of {input} to {places} places"
+        );
+    }
+    assert_eq!(None, Amount::from_scaled(i64::MAX).checked_round(0));
+}
+
+#[test]
+fn parse_errors() {
+    assert!(matches!(
+        "1.00001".parse::<Amount>(),
+        Err(Error::PrecisionExceeded)
+    ));
+    assert!(matches!("".parse::<Amount>(), Err(Error::Malformed)));
+    assert!(matches!(".".parse::<Amount>(), Err(Error::Malformed)));
+    assert!(matches!("1e5".parse::<Amount>(), Err(Error::Malformed)));
+    assert!(matches!("1.2.3".parse::<Amount>(), Err(Error::Malformed)));
+    assert!(matches!(
+        "922337203685478".parse::<Amount>(),
+        Err(Error::Overflow)
+    ));
+}
+
+#[test]
+fn exact_arithmetic() {
+    let twenty: Amount = "20.0".parse().expect("Parsed");
+    let withdrawal: Amount = "19.9999".parse().expect("Parsed");
+    assert_eq!(twenty.checked_sub(withdrawal), Some(Amount::from_scaled(1)));
+    assert_eq!(
+        Amount::from_scaled(i64::MAX).checked_add(Amount::from_scaled(1)),
+        None
+    );
+    assert_eq!(
+        Amount::from_scaled(i64::MIN).checked_sub(Amount::from_scaled(1)),
+        None
+    );
+}
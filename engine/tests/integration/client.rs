@@ -3,7 +3,9 @@ use random_string::generate;
 use std::{env::temp_dir, fs::remove_file};
 
 use engine::{
-    client::{Client, ClientCSV},
+    amount::Amount,
+    client::{Client, ClientCSV, Status, Transition, MAX_TRANSITIONS},
+    currency::Currency,
     write_csv::{write_csv, Output},
 };
 use store::{store::Store, store_db::StoreDBBuilder};
@@ -17,11 +19,14 @@ fn with_store_db() {
         assert!(old_value.is_none(), "No previous value at {}", i);
     }
 
-    let mut keys = store.keys().expect("Keys read");
+    let mut keys = store
+        .keys()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Keys read");
     keys.sort();
-    assert_eq!((1..=10).collect::<Vec<u16>>(), keys);
+    assert_eq!((1..=10).collect::<Vec<u64>>(), keys);
     assert_eq!(
-        (1..=10).collect::<Vec<u16>>(),
+        (1..=10).collect::<Vec<u64>>(),
         keys.iter()
             .map(|id| store.get(id).expect("Gotten").expect("Found").id())
             .collect::<Vec<_>>()
@@ -48,15 +53,21 @@ fn with_store_db() {
 fn store_db_to_csv() {
     let mut store = StoreDBBuilder::new(5).build().expect("Built");
 
-    for i in 1..=10 {
-        let mut client = Client::new(i);
-        let funds = i as f32 / 10.05;
-        client.deposit(funds * 2.).expect("Deposit OK");
-        client.dispute(funds).expect("Dispute OK");
-        store.insert(i, client).expect("Inserted");
+    let usd = Currency::default();
+    for i in 1..=10_i64 {
+        let mut client = Client::new(i.unsigned_abs());
+        let funds = Amount::from_scaled(i * 995);
+        client
+            .deposit(&usd, Amount::from_scaled(i * 1990))
+            .expect("Deposit OK");
+        client.dispute(&usd, funds).expect("Dispute OK");
+        store.insert(client.id(), client).expect("Inserted");
     }
 
-    let mut keys = store.keys().expect("Keys read");
+    let mut keys = store
+        .keys()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Keys read");
     keys.sort();
 
     let tmp_file = format!(
@@ -66,8 +77,13 @@ fn store_db_to_csv() {
     );
     write_csv(
         &Output::File(&tmp_file),
-        keys.iter().map(|id| {
-            <&Client as Into<ClientCSV>>::into(store.get(id).expect("Gotten").expect("Found"))
+        keys.iter().flat_map(|id| {
+            store
+                .get(id)
+                .expect("Gotten")
+                .expect("Found")
+                .clients_csv()
+                .collect::<Vec<_>>()
         }),
     )
     .expect("Written");
@@ -77,3 +93,109 @@ fn store_db_to_csv() {
 
     remove_file(tmp_file).expect("Temporary file removed");
 }
+
+#[test]
+fn currency_column() {
+    let mut client = Client::new(1);
+    for (currency, scaled) in [("USD", 10_000), ("EUR", 25_000), ("GBP", 5_000)] {
+        let currency = currency.parse::<Currency>().expect("Valid currency");
+        client
+            .deposit(&currency, Amount::from_scaled(scaled))
+            .expect("Deposit OK");
+    }
+
+    let tmp_file = format!(
+        "{}/clients_currency_{}.csv",
+        temp_dir().display(),
+        generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+    );
+    write_csv(
+        &Output::File(&tmp_file),
+        client.clients_csv().map(ClientCSV::with_currency),
+    )
+    .expect("Written");
+
+    let etalon_file = format!(
+        "{}/resources/clients_currency.csv",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    assert!(diff(&etalon_file, &tmp_file));
+
+    remove_file(tmp_file).expect("Temporary file removed");
+}
+
+#[test]
+fn transitions() {
+    let usd = Currency::default();
+    let mut client = Client::new(1);
+    client
+        .deposit(&usd, Amount::from_scaled(10_000))
+        .expect("Deposit OK");
+    client
+        .dispute(&usd, Amount::from_scaled(10_000))
+        .expect("Dispute OK");
+    client
+        .chargeback(&usd, Amount::from_scaled(10_000))
+        .expect("Chargeback OK");
+    assert_eq!(client.status(), Status::Locked);
+
+    client.unlock(1).expect("Unlock OK");
+    assert!(client.unlock(2).is_err(), "Not locked anymore");
+    client.close(3).expect("Close OK");
+    assert!(
+        client.deposit(&usd, Amount::from_scaled(1)).is_err(),
+        "Closed"
+    );
+
+    let transitions = store_roundtrip(client).transitions().to_vec();
+    assert_eq!(
+        vec![
+            Transition {
+                transaction_id: 1,
+                from: Status::Locked,
+                to: Status::Active,
+            },
+            Transition {
+                transaction_id: 3,
+                from: Status::Active,
+                to: Status::Closed,
+            },
+        ],
+        transitions
+    );
+}
+
+#[test]
+fn transitions_capped() {
+    let usd = Currency::default();
+    let mut client = Client::new(1);
+    client
+        .deposit(&usd, Amount::from_scaled(20_000))
+        .expect("Deposit OK");
+    for i in 0..20 {
+        client
+            .dispute(&usd, Amount::from_scaled(1))
+            .expect("Dispute OK");
+        client
+            .chargeback(&usd, Amount::from_scaled(1))
+            .expect("Chargeback OK");
+        client.unlock(i).expect("Unlock OK");
+    }
+
+    let transitions = client.transitions();
+    assert_eq!(MAX_TRANSITIONS, transitions.len());
+    assert_eq!(
+        (20 - MAX_TRANSITIONS as u64..20).collect::<Vec<_>>(),
+        transitions
+            .iter()
+            .map(|transition| transition.transaction_id)
+            .collect::<Vec<_>>()
+    );
+}
+
+fn store_roundtrip(client: Client) -> Client {
+    let mut store = StoreDBBuilder::new(0).build().expect("Built");
+    store.insert(client.id(), client).expect("Inserted");
+    store.insert(0, Client::new(0)).expect("Inserted");
+    store.get(&1).expect("Gotten").expect("Found").clone()
+}
@@ -0,0 +1,17 @@
+use engine::{client, error::Error, processor, transaction};
+
+#[test]
+fn codes() {
+    assert_eq!(
+        "client.withdraw_insufficient_funds",
+        Error::Client(client::Error::WithdrawInsufficientFunds).code()
+    );
+    assert_eq!(
+        "transaction.already_charged_back",
+        Error::Transaction(transaction::Error::AlreadyChargedBack).code()
+    );
+    assert_eq!(
+        "processor.transaction_id_duplicate",
+        Error::Processor(processor::Error::TransactionIdDuplicate).code()
+    );
+}
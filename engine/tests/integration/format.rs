@@ -0,0 +1,262 @@
+use file_diff::diff;
+use random_string::generate;
+use std::{
+    env::temp_dir,
+    fs::{create_dir, read_dir, remove_dir_all, remove_file, File},
+};
+
+use engine::{
+    client::Client,
+    format::{write, InputFormat, OutputFormat, Records},
+    processor::Processor,
+    transaction::Transaction,
+    write_csv::Output,
+};
+use store::store_mem::StoreMem;
+
+#[test]
+fn json_lines_input() {
+    let mut processor = Processor::new(StoreMem::new(), StoreMem::new());
+    let mut malformed = vec![];
+    for row in Records::open(
+        &resource("format/transactions.jsonl"),
+        InputFormat::JsonLines,
+    )
+    .expect("Input opened")
+    {
+        match row {
+            Ok(row) => {
+                processor.process(&row.record).ok();
+            }
+            Err(row) => malformed.push(row.line.expect("Line known")),
+        }
+    }
+    assert_eq!(vec![6, 7, 8], malformed);
+
+    assert_written(
+        &processor,
+        OutputFormat::Csv,
+        "processor/accounts_small.csv",
+    );
+}
+
+#[test]
+fn csv_input() {
+    let records = Records::open(
+        &resource("processor/transactions_small.csv"),
+        InputFormat::Csv,
+    )
+    .expect("Input opened");
+    assert_eq!(
+        vec!["type", "client", "tx", "amount"],
+        records.headers().iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![2, 3, 4, 5, 6],
+        records
+            .map(|row| row.expect("Valid record").source.line)
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn continued_input() {
+    let first = Records::open(
+        &resource("processor/transactions_small.csv"),
+        InputFormat::Csv,
+    )
+    .expect("Input opened");
+    let second = Records::continued(
+        File::open(resource("format/transactions_continued.csv")).expect("Input opened"),
+        InputFormat::Csv,
+        &first,
+    )
+    .expect("Input opened");
+    assert_eq!(
+        vec![(6, 1), (7, 2)],
+        second
+            .map(|row| {
+                let row = row.expect("Valid record");
+                (row.record.transaction_id, row.source.line)
+            })
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn concatenated_input() {
+    let records = Records::open(
+        &resource("format/transactions_concatenated.csv"),
+        InputFormat::Csv,
+    )
+    .expect("Input opened");
+    assert_eq!(
+        vec![(1, Ok(2)), (2, Ok(4)), (3, Err(5)), (4, Ok(7))],
+        records
+            .zip(1..)
+            .map(|(row, i)| {
+                (
+                    i,
+                    row.map(|row| row.source.line)
+                        .map_err(|row| row.line.expect("Line known")),
+                )
+            })
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn sources() {
+    // the rows are named after the file and labelled by the header row before them
+    let path = resource("format/transactions_concatenated.csv");
+    let records = Records::open(&path, InputFormat::Csv).expect("Input opened");
+    assert_eq!(
+        [4, 5, 5, 4].map(|headers| (path.clone(), headers)).to_vec(),
+        records
+            .map(|row| match row {
+                Ok(row) => (row.source.input, row.source.headers.len()),
+                Err(malformed) => (malformed.input, malformed.headers.len()),
+            })
+            .map(|(input, headers)| (input.expect("Input named").to_string(), headers))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn header_field_in_data_row() {
+    let records = Records::from_reader(
+        "type,client,tx,amount,note\n\
+         deposit,1,1,1.0,type\n\
+         type,client,tx,amount,note\n\
+         deposit,1,2,2.0,\n"
+            .as_bytes(),
+        InputFormat::Csv,
+    )
+    .expect("Input opened");
+    assert_eq!(
+        vec![(1, 2), (2, 4)],
+        records
+            .map(|row| {
+                let row = row.expect("Valid record");
+                (row.record.transaction_id, row.source.line)
+            })
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn outputs() {
+    let mut processor = Processor::new(StoreMem::new(), StoreMem::new());
+    for row in Records::open(
+        &resource("processor/transactions_small.csv"),
+        InputFormat::Csv,
+    )
+    .expect("Input opened")
+    {
+        processor.process(&row.expect("Valid record").record).ok();
+    }
+
+    assert_written(&processor, OutputFormat::JsonLines, "format/accounts.jsonl");
+    assert_written(&processor, OutputFormat::Json, "format/accounts.json");
+}
+
+#[test]
+fn headerless_rounded() {
+    let mut processor = Processor::new(StoreMem::new(), StoreMem::new());
+    for row in Records::open(
+        &resource("processor/transactions_partial_dispute.csv"),
+        InputFormat::Csv,
+    )
+    .expect("Input opened")
+    {
+        processor.process(&row.expect("Valid record").record).ok();
+    }
+
+    // the output is renamed into place, nothing else is left in the directory
+    let tmp_dir = format!(
+        "{}/accounts_{}",
+        temp_dir().display(),
+        generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+    );
+    create_dir(&tmp_dir).expect("Temporary directory created");
+    let tmp_file = format!("{tmp_dir}/accounts.csv");
+    write(
+        &Output::File(&tmp_file),
+        OutputFormat::Csv,
+        false,
+        processor
+            .clients_csv()
+            .expect("Clients read")
+            .map(|client| client.with_precision(2)),
+    )
+    .expect("Written");
+
+    assert!(diff(&resource("format/accounts_rounded.csv"), &tmp_file));
+    assert_eq!(1, read_dir(&tmp_dir).expect("Directory read").count());
+
+    remove_dir_all(tmp_dir).expect("Temporary directory removed");
+}
+
+#[test]
+fn rounded_total() {
+    let mut processor = Processor::new(StoreMem::new(), StoreMem::new());
+    for row in Records::open(
+        &resource("format/transactions_rounded.csv"),
+        InputFormat::Csv,
+    )
+    .expect("Input opened")
+    {
+        processor.process(&row.expect("Valid record").record).ok();
+    }
+
+    // 0.0050 available and 0.0050 held are written as 0.01 each and 0.02 in total
+    let tmp_file = format!(
+        "{}/accounts_{}",
+        temp_dir().display(),
+        generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+    );
+    write(
+        &Output::File(&tmp_file),
+        OutputFormat::Csv,
+        true,
+        processor
+            .clients_csv()
+            .expect("Clients read")
+            .map(|client| client.with_precision(2)),
+    )
+    .expect("Written");
+
+    assert!(diff(
+        &resource("format/accounts_rounded_total.csv"),
+        &tmp_file
+    ));
+
+    remove_file(tmp_file).expect("Temporary file removed");
+}
+
+fn assert_written(
+    processor: &Processor<StoreMem<u64, Client>, StoreMem<u64, Transaction>>,
+    format: OutputFormat,
+    etalon: &str,
+) {
+    let tmp_file = format!(
+        "{}/accounts_{}",
+        temp_dir().display(),
+        generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+    );
+    write(
+        &Output::File(&tmp_file),
+        format,
+        true,
+        processor.clients_csv().expect("Clients read"),
+    )
+    .expect("Written");
+
+    assert!(diff(&resource(etalon), &tmp_file), "{etalon} written");
+
+    remove_file(tmp_file).expect("Temporary file removed");
+}
+
+fn resource(path: &str) -> String {
+    format!("{}/resources/{path}", env!("CARGO_MANIFEST_DIR"))
+}
@@ -0,0 +1,53 @@
+use engine::{currency::Currency, format::parse_json, input::Record};
+
+#[test]
+fn wide_ids() {
+    let record = parse("transfer,18446744073709551615,18446744073709551614,1.0,70000")
+        .expect("Valid record");
+    assert_eq!(u64::MAX, record.client_id);
+    assert_eq!(u64::MAX - 1, record.transaction_id);
+    assert_eq!(Some(70_000), record.recipient_id);
+}
+
+#[test]
+fn id_overflow() {
+    for row in [
+        "deposit,18446744073709551616,1,1.0,",
+        "deposit,1,18446744073709551616,1.0,",
+        "transfer,1,1,1.0,18446744073709551616",
+    ] {
+        let error = parse(row).expect_err("Overflow");
+        assert!(
+            error.contains("ID \"18446744073709551616\" overflows"),
+            "Clear error for {}: {}",
+            row,
+            error
+        );
+    }
+}
+
+#[test]
+fn json_currency() {
+    let currency = |value: &str| {
+        let object = format!(
+            "{{\"type\": \"deposit\", \"client\": 1, \"tx\": 1, \"amount\": 1.0, \"currency\": {value}}}"
+        );
+        parse_json(object.as_bytes()).map(|record| record.currency)
+    };
+    // an empty string stands for the default currency, the same as an empty column of CSV
+    assert_eq!(None, currency("\"\"").expect("Valid record"));
+    assert_eq!(None, currency("null").expect("Valid record"));
+    assert_eq!(
+        Some("EUR".parse::<Currency>().expect("Valid currency")),
+        currency("\" eur \"").expect("Valid record")
+    );
+    assert!(currency("\"E-U-R\"").is_err());
+}
+
+fn parse(row: &str) -> Result<Record, String> {
+    csv::Reader::from_reader(format!("type,client,tx,amount,to_client\n{}\n", row).as_bytes())
+        .deserialize()
+        .next()
+        .expect("One record")
+        .map_err(|error| error.to_string())
+}
@@ -0,0 +1,115 @@
+use random_string::generate;
+use std::{env::temp_dir, fs::remove_dir_all};
+
+use engine::{
+    amount::Amount,
+    client::{Client, ClientCSV},
+    journal::{entries, Journal, JournalEntry, Sequence},
+    processor::Processor,
+    transaction::Transaction,
+};
+use store::{store_db::StoreDBBuilder, store_mem::StoreMem};
+
+#[test]
+fn replay() {
+    let journal_path = tmp_journal_path();
+    let (expected, mut journal) = process("medium", open_journal(&journal_path));
+
+    let mut processor = new_processor();
+    processor.replay(&mut *journal).expect("Replayed");
+    assert_eq!(expected, clients(&processor));
+
+    drop(journal);
+    remove_dir_all(journal_path).expect("Journal removed");
+}
+
+#[test]
+fn replay_mismatch() {
+    let journal_path = tmp_journal_path();
+    let (_, mut journal) = process("small", open_journal(&journal_path));
+
+    let (sequence, mut entry) = entries(&mut *journal)
+        .next()
+        .expect("First entry")
+        .expect("Entry read");
+    entry.record.amount = Some(Amount::from_scaled(1));
+    journal.insert(sequence, entry).expect("Tampered");
+
+    let mut processor = new_processor();
+    assert_eq!(
+        "Processor(JournalMismatch)",
+        format!(
+            "{:?}",
+            processor.replay(&mut *journal).expect_err("Mismatch")
+        )
+    );
+
+    drop(journal);
+    remove_dir_all(journal_path).expect("Journal removed");
+}
+
+#[test]
+fn sequence_continued() {
+    let journal_path = tmp_journal_path();
+    let (_, mut journal) = process("small", open_journal(&journal_path));
+    let appended = entries(&mut *journal).count();
+
+    let (_, mut journal) = process("small", journal);
+    assert_eq!(
+        (0..appended as u64 * 2).map(Sequence).collect::<Vec<_>>(),
+        entries(&mut *journal)
+            .map(|entry| entry.expect("Entry read").0)
+            .collect::<Vec<_>>()
+    );
+
+    drop(journal);
+    remove_dir_all(journal_path).expect("Journal removed");
+}
+
+/// Processes the dataset journaling the accepted records, returns the resulting accounts
+/// along with the journal. The same handle is passed around rather than the database being
+/// reopened, since sled releases its lock asynchronously after the handle is dropped
+fn process(dataset: &str, journal: Journal) -> (Vec<ClientCSV>, Journal) {
+    let mut processor = new_processor().set_journal(journal).expect("Journal set");
+
+    let mut reader = csv::Reader::from_path(format!(
+        "{}/resources/processor/transactions_{}.csv",
+        env!("CARGO_MANIFEST_DIR"),
+        dataset
+    ))
+    .expect("CSV reader created");
+    for record in reader.deserialize() {
+        processor.process(&record.expect("Valid record")).ok();
+    }
+    processor.flush().expect("Flushed");
+
+    let clients = clients(&processor);
+    (clients, processor.into_journal().expect("Journal set"))
+}
+
+fn new_processor() -> Processor<StoreMem<u64, Client>, StoreMem<u64, Transaction>> {
+    Processor::new(StoreMem::new(), StoreMem::new())
+}
+
+fn clients(
+    processor: &Processor<StoreMem<u64, Client>, StoreMem<u64, Transaction>>,
+) -> Vec<ClientCSV> {
+    processor.clients_csv().expect("Clients read").collect()
+}
+
+fn open_journal(path: &str) -> Journal {
+    Box::new(
+        StoreDBBuilder::new(5)
+            .set_db_path(path.to_string())
+            .build::<Sequence, JournalEntry>()
+            .expect("Journal opened"),
+    )
+}
+
+fn tmp_journal_path() -> String {
+    format!(
+        "{}/journal_{}",
+        temp_dir().display(),
+        generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+    )
+}
@@ -0,0 +1,147 @@
+use engine::{
+    amount::Amount,
+    client::Client,
+    currency::Currency,
+    ledger::{Account, Balance, Ledger, Posting},
+    processor::{NegativeBalances, Processor, WithdrawalDisputes},
+    transaction::Transaction,
+};
+use store::store_mem::StoreMem;
+
+type TestProcessor = Processor<StoreMem<u64, Client>, StoreMem<u64, Transaction>>;
+
+#[test]
+fn reconciled() {
+    for (dataset, configure) in [
+        ("small", identity as fn(TestProcessor) -> TestProcessor),
+        ("medium", identity),
+        ("transfer", identity),
+        ("partial_dispute", identity),
+        ("currency", identity),
+        ("withdrawal_dispute", |processor: TestProcessor| {
+            processor.set_withdrawal_disputes(WithdrawalDisputes::Reverse)
+        }),
+        ("negative_balance", |processor: TestProcessor| {
+            processor.set_negative_balances(NegativeBalances::Allow)
+        }),
+        ("admin", |processor: TestProcessor| {
+            processor.set_admin_input(true)
+        }),
+    ] {
+        let processor = process(dataset, configure);
+        processor
+            .reconcile_ledger()
+            .unwrap_or_else(|error| panic!("{} reconciled: {:?}", dataset, error));
+        let total = processor
+            .ledger()
+            .expect("Ledger set")
+            .total(&Currency::default());
+        assert_eq!(total.debit, total.credit, "{} balanced", dataset);
+    }
+}
+
+#[test]
+fn transfer_chargeback() {
+    let ledger = process("transfer", identity)
+        .ledger()
+        .expect("Ledger set")
+        .ledger_csv()
+        .map(|row| {
+            format!(
+                "{},{},{},{}",
+                row.currency, row.account, row.debit, row.credit
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            "USD,customer-available:1,4.0000,14.0000",
+            "USD,customer-available:2,8.0000,13.0000",
+            // the transfer charged back is refunded to 4, which has been locked by then
+            "USD,customer-available:4,5.0000,10.0000",
+            "USD,customer-available:5,3.0000,3.0000",
+            "USD,customer-held:2,8.0000,8.0000",
+            "USD,customer-held:4,2.0000,2.0000",
+            "USD,customer-held:5,3.0000,3.0000",
+            "USD,funding-source,22.0000,2.0000",
+        ],
+        ledger
+    );
+}
+
+#[test]
+fn mismatch() {
+    let mut ledger = Ledger::default();
+    ledger
+        .post(Posting {
+            currency: Currency::default(),
+            debit: Account::FundingSource,
+            credit: Account::CustomerAvailable(1),
+            amount: Amount::from_scaled(10_000),
+        })
+        .expect("Posted");
+    assert_eq!(
+        Balance {
+            debit: Amount::ZERO,
+            credit: Amount::from_scaled(10_000),
+        },
+        ledger.balance(&Currency::default(), Account::CustomerAvailable(1))
+    );
+    ledger.trial_balance().expect("Balanced");
+    assert!(ledger.reconcile(&Client::new(1)).is_err(), "Mismatch");
+}
+
+#[test]
+fn unbalanced() {
+    let mut ledger = Ledger::default();
+    for (debit, credit) in [
+        (Account::FundingSource, Account::CustomerAvailable(1)),
+        (Account::CustomerAvailable(1), Account::CustomerHeld(1)),
+    ] {
+        ledger
+            .post(Posting {
+                currency: Currency::default(),
+                debit,
+                credit,
+                amount: Amount::from_scaled(10_000),
+            })
+            .expect("Posted");
+    }
+    ledger.trial_balance().expect("Balanced");
+
+    // a corrupted posting that names the same account on both sides, only one side of it
+    // lands in the account, while the totals of the postings count both
+    ledger
+        .post(Posting {
+            currency: Currency::default(),
+            debit: Account::CustomerHeld(1),
+            credit: Account::CustomerHeld(1),
+            amount: Amount::from_scaled(10_000),
+        })
+        .expect("Posted");
+    assert_eq!(
+        "Unbalanced",
+        format!("{:?}", ledger.trial_balance().expect_err("Unbalanced"))
+    );
+}
+
+fn identity(processor: TestProcessor) -> TestProcessor {
+    processor
+}
+
+fn process(dataset: &str, configure: fn(TestProcessor) -> TestProcessor) -> TestProcessor {
+    let mut processor =
+        configure(Processor::new(StoreMem::new(), StoreMem::new()).set_ledger(Ledger::default()));
+
+    let mut reader = csv::Reader::from_path(format!(
+        "{}/resources/processor/transactions_{}.csv",
+        env!("CARGO_MANIFEST_DIR"),
+        dataset
+    ))
+    .expect("CSV reader created");
+    for record in reader.deserialize() {
+        processor.process(&record.expect("Valid record")).ok();
+    }
+
+    processor
+}
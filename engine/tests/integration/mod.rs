@@ -1,3 +1,10 @@
+mod amount;
 mod client;
+mod error;
+mod format;
+mod input;
+mod journal;
+mod ledger;
 mod processor;
+mod sharded_processor;
 mod transaction;
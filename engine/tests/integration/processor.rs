@@ -1,17 +1,25 @@
 use file_diff::diff;
-use itertools::Itertools;
 use random_string::generate;
 use std::{env::temp_dir, fs::remove_file};
 
 use engine::{
-    processor::Processor,
+    amount::Amount,
+    client::Client,
+    currency::Currency,
+    processor::{NegativeBalances, Processor, WithdrawalDisputes},
+    stats::{ProcessingStats, Totals},
+    transaction::Transaction,
     write_csv::{write_csv, Output},
 };
-use store::{store_db::StoreDBBuilder, store_mem::StoreMem};
+use store::{
+    store::Store,
+    store_db::{StoreDB, StoreDBBuilder},
+    store_mem::StoreMem,
+};
 
 #[test]
 fn small() {
-    test_processor("small", 3, vec!["WithdrawInsufficientFunds"]);
+    test_processor("small", 3, vec!["Client(WithdrawInsufficientFunds)"]);
 }
 
 #[test]
@@ -20,31 +28,214 @@ fn medium() {
         "medium",
         5,
         vec![
-            "WithdrawInsufficientFunds",
-            "AlreadyInDispute",
-            "ClientIdMismatch",
-            "ClientLocked",
-            "AmountNegative",
-            "AlreadyChargedBack",
-            "DisputeInsufficientFunds",
-            "DisputeWithdrawal",
-            "ResolveNonDisputed",
-            "ChargeBackNonDisputed",
-            "AmountUnnecessary",
-            "AmountUnspecified",
-            "TransactionIdDuplicate",
-            "TransactionNotFound",
+            "Client(WithdrawInsufficientFunds)",
+            "Transaction(AlreadyInDispute)",
+            "Processor(ClientIdMismatch)",
+            "Client(ClientLocked)",
+            "Processor(AmountNegative)",
+            "Transaction(AlreadyChargedBack)",
+            "Client(DisputeInsufficientFunds)",
+            "Transaction(DisputeWithdrawal)",
+            "Transaction(ResolveNonDisputed)",
+            "Transaction(ChargeBackNonDisputed)",
+            "Processor(TransactionNotFound)",
+            "Processor(AmountUnspecified)",
+            "Processor(TransactionIdDuplicate)",
+            "Processor(TransactionNotFound)",
+        ],
+    );
+}
+
+#[test]
+fn transfer() {
+    test_processor(
+        "transfer",
+        5,
+        vec![
+            "Client(WithdrawInsufficientFunds)",
+            "Processor(RecipientUnspecified)",
+            "Processor(TransferToSelf)",
+            "Processor(TransactionIdDuplicate)",
+            "Processor(RecipientUnnecessary)",
+            "Processor(ClientIdMismatch)",
+            "Client(ClientLocked)",
+            "Client(WithdrawInsufficientFunds)",
+            "Processor(AmountUnspecified)",
+        ],
+    );
+}
+
+#[test]
+fn partial_dispute() {
+    test_processor(
+        "partial_dispute",
+        5,
+        vec![
+            "Transaction(AmountExceedsUndisputed)",
+            "Transaction(AmountExceedsDisputed)",
+            "Processor(AmountNegative)",
+            "Transaction(AlreadyInDispute)",
+            "Transaction(ResolveNonDisputed)",
+            "Client(ClientLocked)",
+            "Client(ClientLocked)",
+        ],
+    );
+}
+
+#[test]
+fn withdrawal_dispute_reverse() {
+    test_processor_with(
+        "withdrawal_dispute",
+        "withdrawal_dispute",
+        5,
+        vec![
+            "Transaction(ChargeBackNonDisputed)",
+            "Transaction(AlreadyChargedBack)",
+        ],
+        |processor| processor.set_withdrawal_disputes(WithdrawalDisputes::Reverse),
+    );
+}
+
+#[test]
+fn withdrawal_dispute_reject() {
+    test_processor_with(
+        "withdrawal_dispute",
+        "withdrawal_dispute_reject",
+        5,
+        vec![
+            "Transaction(DisputeWithdrawal)",
+            "Transaction(ChargeBackNonDisputed)",
+            "Transaction(DisputeWithdrawal)",
+            "Transaction(ResolveNonDisputed)",
+            "Transaction(ChargeBackNonDisputed)",
+            "Transaction(DisputeWithdrawal)",
+            "Transaction(DisputeWithdrawal)",
+        ],
+        |processor| processor.set_withdrawal_disputes(WithdrawalDisputes::Reject),
+    );
+}
+
+#[test]
+fn negative_balance_allow() {
+    test_processor_with(
+        "negative_balance",
+        "negative_balance",
+        5,
+        vec![],
+        |processor| processor.set_negative_balances(NegativeBalances::Allow),
+    );
+}
+
+#[test]
+fn negative_balance_reject() {
+    test_processor_with(
+        "negative_balance",
+        "negative_balance_reject",
+        5,
+        vec![
+            "Client(DisputeInsufficientFunds)",
+            "Transaction(ChargeBackNonDisputed)",
+            "Client(DisputeInsufficientFunds)",
+            "Transaction(ResolveNonDisputed)",
+        ],
+        |processor| processor.set_negative_balances(NegativeBalances::Reject),
+    );
+}
+
+#[test]
+fn admin() {
+    test_processor_with(
+        "admin",
+        "admin",
+        5,
+        vec![
+            "Client(ClientLocked)",
+            "Client(ClientNotLocked)",
+            "Processor(ClientNotFound)",
+            "Processor(AmountUnnecessary)",
+            "Client(ClientClosed)",
+            "Client(ClientClosed)",
+            "Client(ClientClosed)",
+        ],
+        |processor| processor.set_admin_input(true),
+    );
+}
+
+#[test]
+fn admin_disabled() {
+    test_processor_with(
+        "admin",
+        "admin_disabled",
+        5,
+        vec![
+            "Client(ClientLocked)",
+            "Processor(AdminInputDisabled)",
+            "Processor(AdminInputDisabled)",
+            "Client(ClientLocked)",
+            "Processor(AdminInputDisabled)",
+            "Processor(AdminInputDisabled)",
+            "Processor(AdminInputDisabled)",
+            "Processor(AdminInputDisabled)",
+            "Processor(AdminInputDisabled)",
+        ],
+        |processor| processor,
+    );
+}
+
+#[test]
+fn currency() {
+    test_processor_with(
+        "currency",
+        "currency",
+        5,
+        vec![
+            "Processor(CurrencyMismatch)",
+            "Client(WithdrawInsufficientFunds)",
         ],
+        |processor| processor.set_currency_column(true),
+    );
+}
+
+#[test]
+fn currency_default() {
+    test_processor_with(
+        "currency",
+        "currency_default",
+        5,
+        vec![
+            "Processor(CurrencyMismatch)",
+            "Client(WithdrawInsufficientFunds)",
+        ],
+        |processor| {
+            processor
+                .set_default_currency("gbp".parse().expect("Valid currency"))
+                .set_currency_column(true)
+        },
     );
 }
 
 fn test_processor(dataset: &str, txbuffer: usize, errors: Vec<&str>) {
-    let mut processor = Processor::new(
+    test_processor_with(dataset, dataset, txbuffer, errors, |processor| processor);
+}
+
+fn test_processor_with<CS, TS>(
+    dataset: &str,
+    accounts: &str,
+    txbuffer: usize,
+    errors: Vec<&str>,
+    configure: impl FnOnce(
+        Processor<StoreMem<u64, Client>, StoreDB<u64, Transaction>>,
+    ) -> Processor<CS, TS>,
+) where
+    CS: Store<u64, Client>,
+    TS: Store<u64, Transaction>,
+{
+    let mut processor = configure(Processor::new(
         StoreMem::new(),
         StoreDBBuilder::new(txbuffer)
             .build()
             .expect("StoreDB created"),
-    );
+    ));
 
     let mut reader = csv::Reader::from_path(format!(
         "{}/resources/processor/transactions_{}.csv",
@@ -68,25 +259,107 @@ fn test_processor(dataset: &str, txbuffer: usize, errors: Vec<&str>) {
     let tmp_file = format!(
         "{}/accounts_{}_{}.csv",
         temp_dir().display(),
-        dataset,
+        accounts,
         generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
     );
 
     write_csv(
         &Output::File(&tmp_file),
-        processor
-            .clients_csv()
-            .expect("Clients read")
-            .sorted_by(|a, b| Ord::cmp(&a.id, &b.id)),
+        processor.clients_csv().expect("Clients read"),
     )
     .expect("Written");
 
     let etalon_file = format!(
         "{}/resources/processor/accounts_{}.csv",
         env!("CARGO_MANIFEST_DIR"),
-        dataset
+        accounts
     );
     assert!(diff(&etalon_file, &tmp_file));
 
     remove_file(tmp_file).expect("Temporary file removed");
 }
+
+#[test]
+fn stats() {
+    let mut processor = Processor::new(StoreMem::new(), StoreMem::new());
+    let mut reader = csv::Reader::from_path(format!(
+        "{}/resources/processor/transactions_partial_dispute.csv",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .expect("CSV reader created");
+    for record in reader.deserialize() {
+        processor.process(&record.expect("Valid record")).ok();
+    }
+
+    let mut stats = processor.stats().expect("Stats read");
+    stats.add_rejected("input.malformed", 1);
+    assert_eq!((26, 18, 8), (stats.read, stats.accepted, stats.rejected_total()));
+    assert_eq!(
+        vec![
+            ("client.client_locked", 2),
+            ("input.malformed", 1),
+            ("processor.amount_negative", 1),
+            ("transaction.already_in_dispute", 1),
+            ("transaction.amount_exceeds_disputed", 1),
+            ("transaction.amount_exceeds_undisputed", 1),
+            ("transaction.resolve_non_disputed", 1),
+        ],
+        stats.rejected.into_iter().collect::<Vec<_>>()
+    );
+    assert_eq!(
+        Totals {
+            deposited: "35".parse().expect("Valid amount"),
+            disputed: "26".parse().expect("Valid amount"),
+            resolved: "16".parse().expect("Valid amount"),
+            charged_back: "10".parse().expect("Valid amount"),
+            ..Totals::default()
+        },
+        stats.totals[&Currency::default()]
+    );
+    assert_eq!(3, stats.locked_accounts);
+    assert!(stats.records_per_second > 0.0);
+}
+
+#[test]
+fn stats_locked_closed() {
+    let mut processor = Processor::new(StoreMem::new(), StoreMem::new()).set_admin_input(true);
+    let mut reader = csv::Reader::from_reader(
+        "type,client,tx,amount\n\
+         deposit,1,1,1.0\n\
+         dispute,1,1,\n\
+         chargeback,1,1,\n\
+         close,1,100,\n\
+         deposit,2,2,1.0\n\
+         close,2,101,\n"
+            .as_bytes(),
+    );
+    for record in reader.deserialize() {
+        processor
+            .process(&record.expect("Valid record"))
+            .expect("Accepted");
+    }
+
+    // the account closed after a chargeback is still locked, the one closed without is not
+    assert_eq!(1, processor.stats().expect("Stats read").locked_accounts);
+}
+
+#[test]
+fn stats_overflowed() {
+    let mut stats = ProcessingStats::default();
+    stats.totals.insert(
+        Currency::default(),
+        Totals {
+            deposited: Amount::from_scaled(i64::MAX - 1),
+            ..Totals::default()
+        },
+    );
+    let shard = stats.clone();
+    stats.merge(&shard);
+
+    let totals = stats.totals[&Currency::default()];
+    assert_eq!(
+        (Amount::from_scaled(i64::MAX), true),
+        (totals.deposited, totals.overflowed)
+    );
+    assert!(stats.to_string().contains("overflowed"));
+}
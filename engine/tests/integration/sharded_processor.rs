@@ -0,0 +1,186 @@
+use file_diff::diff;
+use random_string::generate;
+use std::{
+    env::temp_dir,
+    fs::remove_file,
+    sync::{Arc, Mutex},
+};
+
+use engine::{
+    input::Record,
+    processor::Processor,
+    sharded_processor::ShardedProcessor,
+    write_csv::{write_csv, Output},
+};
+use store::{store_db::StoreDBBuilder, store_mem::StoreMem};
+
+#[test]
+fn small() {
+    let errors = test_sharded_processor("small", 3);
+    assert_eq!(vec!["Client(WithdrawInsufficientFunds)"], errors);
+}
+
+#[test]
+fn medium() {
+    // the order of the errors across the shards isn't deterministic, neither is the outcome
+    // of the transaction ID 5 that is reused by two clients in different shards,
+    // but the balances are
+    let errors = test_sharded_processor("medium", 2);
+    assert_eq!(14, errors.len());
+}
+
+#[test]
+fn transaction_id_unique_across_shards() {
+    let mut processors = (0..2)
+        .map(|_| Processor::new(StoreMem::new(), StoreMem::new()))
+        .collect::<Vec<_>>();
+    processors[1]
+        .process(&record("deposit,1,1,1.0,"))
+        .expect("Deposit OK");
+
+    let errors = Arc::new(Mutex::new(vec![]));
+    let errors_clone = Arc::clone(&errors);
+    // the line of the input is handed back along with the rejected record
+    let mut processor =
+        ShardedProcessor::new(processors, StoreMem::new(), move |record, line, error| {
+            errors_clone
+                .lock()
+                .unwrap()
+                .push(format!("{} at {}: {:?}", record.client_id, line, error));
+        })
+        .expect("ShardedProcessor created");
+
+    processor
+        .process(record("deposit,2,1,2.0,"), 1)
+        .expect("Queued");
+    processor
+        .process(record("deposit,2,2,2.0,"), 2)
+        .expect("Queued");
+    processor.join().expect("Joined");
+
+    assert_eq!(
+        vec!["2 at 1: Processor(TransactionIdDuplicate)"],
+        *errors.lock().unwrap()
+    );
+    assert!(matches!(
+        processor.process(record("deposit,2,3,2.0,"), 3),
+        Err(error) if error.is_fatal()
+    ));
+}
+
+#[test]
+fn transfer_within_shard() {
+    let processors = (0..2)
+        .map(|_| Processor::new(StoreMem::new(), StoreMem::new()))
+        .collect::<Vec<_>>();
+    let errors = Arc::new(Mutex::new(vec![]));
+    let errors_clone = Arc::clone(&errors);
+    let mut processor =
+        ShardedProcessor::new(processors, StoreMem::new(), move |record, (), error| {
+            errors_clone
+                .lock()
+                .unwrap()
+                .push(format!("{}: {:?}", record.transaction_id, error));
+        })
+        .expect("ShardedProcessor created");
+
+    for row in [
+        "deposit,1,1,5.0,",
+        "transfer,1,2,1.0,2",
+        "transfer,1,3,1.0,3",
+    ] {
+        processor.process(record(row), ()).expect("Queued");
+    }
+
+    assert_eq!(
+        vec![(1, "4.0000".to_string()), (3, "1.0000".to_string())],
+        processor
+            .clients_csv()
+            .expect("Clients read")
+            .map(|client| {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(vec![]);
+                writer.serialize(&client).expect("Serialized");
+                let row = String::from_utf8(writer.into_inner().expect("Flushed")).unwrap();
+                (client.id, row.split(',').nth(1).unwrap().to_string())
+            })
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec!["2: Processor(TransferAcrossShards)"],
+        *errors.lock().unwrap()
+    );
+}
+
+fn record(row: &str) -> Record {
+    csv::Reader::from_reader(format!("type,client,tx,amount,to_client\n{}", row).as_bytes())
+        .deserialize()
+        .next()
+        .expect("Row read")
+        .expect("Valid record")
+}
+
+fn test_sharded_processor(dataset: &str, shards: usize) -> Vec<String> {
+    let processors = (0..shards)
+        .map(|_| {
+            Processor::new(
+                StoreMem::new(),
+                StoreDBBuilder::new(5).build().expect("StoreDB created"),
+            )
+        })
+        .collect();
+
+    let errors = Arc::new(Mutex::new(vec![]));
+    let errors_clone = Arc::clone(&errors);
+    // the registry keeps only a few of the accepted IDs in memory, the rest of them are on disk
+    let registry = StoreDBBuilder::new(2).build().expect("StoreDB created");
+    let mut processor = ShardedProcessor::new(processors, registry, move |_, (), error| {
+        errors_clone.lock().unwrap().push(format!("{:?}", error));
+    })
+    .expect("ShardedProcessor created");
+
+    let mut reader = csv::Reader::from_path(format!(
+        "{}/resources/processor/transactions_{}.csv",
+        env!("CARGO_MANIFEST_DIR"),
+        dataset
+    ))
+    .expect("CSV reader created");
+
+    let mut read = 0;
+    for record in reader.deserialize() {
+        processor
+            .process(record.expect("Valid record"), ())
+            .expect("Queued");
+        read += 1;
+    }
+
+    let tmp_file = format!(
+        "{}/accounts_sharded_{}_{}.csv",
+        temp_dir().display(),
+        dataset,
+        generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+    );
+
+    write_csv(
+        &Output::File(&tmp_file),
+        processor.clients_csv().expect("Clients read"),
+    )
+    .expect("Written");
+
+    let etalon_file = format!(
+        "{}/resources/processor/accounts_{}.csv",
+        env!("CARGO_MANIFEST_DIR"),
+        dataset
+    );
+    assert!(diff(&etalon_file, &tmp_file));
+
+    remove_file(tmp_file).expect("Temporary file removed");
+
+    // every rejection is counted, including the ones made by the shards rather than the processors
+    let errors = errors.lock().unwrap().clone();
+    let stats = processor.stats().expect("Stats read");
+    assert_eq!(read, stats.read);
+    assert_eq!(errors.len() as u64, stats.rejected_total());
+    errors
+}
@@ -3,6 +3,8 @@ use random_string::generate;
 use std::{env::temp_dir, fs::remove_file};
 
 use engine::{
+    amount::Amount,
+    currency::Currency,
     transaction::{Action, Transaction},
     write_csv::{write_csv, Output},
 };
@@ -14,16 +16,28 @@ fn with_store_db() {
 
     for i in 1..=10 {
         let old_value = store
-            .insert(i, Transaction::new(i, 10, 0.1, Action::Deposit))
+            .insert(
+                i,
+                Transaction::new(
+                    i,
+                    10,
+                    Currency::default(),
+                    Amount::from_scaled(1_000),
+                    Action::Deposit,
+                ),
+            )
             .expect("Inserted");
         assert!(old_value.is_none(), "No previous value at {}", i);
     }
 
-    let mut keys = store.keys().expect("Keys read");
+    let mut keys = store
+        .keys()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Keys read");
     keys.sort();
-    assert_eq!((1..=10).collect::<Vec<u32>>(), keys);
+    assert_eq!((1..=10).collect::<Vec<u64>>(), keys);
     assert_eq!(
-        (1..=10).collect::<Vec<u32>>(),
+        (1..=10).collect::<Vec<u64>>(),
         keys.iter()
             .map(|id| store.get(id).expect("Gotten").expect("Found").id())
             .collect::<Vec<_>>()
@@ -36,7 +50,16 @@ fn with_store_db() {
     }
     for i in 1..=10 {
         let old_value = store
-            .insert(i, Transaction::new(i + 1, 10, 0.2, Action::Withdrawal))
+            .insert(
+                i,
+                Transaction::new(
+                    i + 1,
+                    10,
+                    Currency::default(),
+                    Amount::from_scaled(2_000),
+                    Action::Withdrawal,
+                ),
+            )
             .expect("Inserted");
         assert!(old_value.is_some(), "Value exists at {}", i);
         assert_eq!(old_value.unwrap().id(), i, "Correct value at {}", i);
@@ -53,19 +76,28 @@ fn store_db_to_csv() {
     let mut store = StoreDBBuilder::new(5).build().expect("Built");
 
     for i in 1..=10 {
-        let mut tx = Transaction::new(i, 10, 0.1, Action::Deposit);
+        let mut tx = Transaction::new(
+            i,
+            10,
+            Currency::default(),
+            Amount::from_scaled(1_000),
+            Action::Deposit,
+        );
         if i % 2 == 0 {
-            tx.dispute().expect("Dispute OK");
+            tx.dispute(None).expect("Dispute OK");
             if i % 3 == 0 {
-                tx.resolve().expect("Resolve OK");
+                tx.resolve(None).expect("Resolve OK");
             } else if i % 4 == 0 {
-                tx.chargeback().expect("Chargeback OK");
+                tx.chargeback(None).expect("Chargeback OK");
             }
         }
         store.insert(i, tx).expect("Inserted");
     }
 
-    let mut keys = store.keys().expect("Keys read");
+    let mut keys = store
+        .keys()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Keys read");
     keys.sort();
 
     let tmp_file = format!(
@@ -85,3 +117,42 @@ fn store_db_to_csv() {
 
     remove_file(tmp_file).expect("Temporary file removed");
 }
+
+#[test]
+fn slices() {
+    let amount = |amount: &str| amount.parse::<Amount>().expect("Valid amount");
+    let mut tx = Transaction::new(1, 10, Currency::default(), amount("10"), Action::Deposit);
+
+    assert_eq!(Ok(amount("3")), outcome(tx.dispute(Some(amount("3")))));
+    assert_eq!(Ok(amount("2")), outcome(tx.dispute(Some(amount("2")))));
+    assert_eq!(
+        Err("AmountZero".to_string()),
+        outcome(tx.dispute(Some(Amount::ZERO)))
+    );
+    assert_eq!(
+        Err("AmountMatchesNoSlice".to_string()),
+        outcome(tx.resolve(Some(amount("4"))))
+    );
+    assert_eq!(
+        Err("AmountExceedsDisputed".to_string()),
+        outcome(tx.resolve(Some(amount("6"))))
+    );
+    assert_eq!(
+        Err("AmountZero".to_string()),
+        outcome(tx.chargeback(Some(Amount::ZERO)))
+    );
+
+    assert_eq!(Ok(amount("2")), outcome(tx.resolve(Some(amount("2")))));
+    assert_eq!(&[amount("3")], tx.slices());
+    assert_eq!(Ok(amount("3")), outcome(tx.chargeback(Some(amount("3")))));
+    assert_eq!(amount("3"), tx.charged_back());
+
+    assert_eq!(Ok(amount("7")), outcome(tx.dispute(None)));
+    assert_eq!(Ok(amount("7")), outcome(tx.resolve(None)));
+    assert!(tx.slices().is_empty());
+    assert_eq!(Amount::ZERO, tx.disputed());
+}
+
+fn outcome<E: std::fmt::Debug>(result: Result<Amount, E>) -> Result<Amount, String> {
+    result.map_err(|error| format!("{error:?}"))
+}
@@ -0,0 +1,324 @@
+#![deny(rust_2018_idioms)]
+#![deny(clippy::correctness)]
+#![deny(clippy::perf)]
+#![deny(clippy::all)]
+#![deny(clippy::pedantic)]
+
+//! HTTP service on top of the [Simple Payment Engine](../engine/index.html)
+//!
+//! * `POST /transactions` processes a single record, given either as a JSON object or as
+//!   a CSV header row followed by the row of the record (`Content-Type: text/csv`)
+//! * `GET /clients` lists the accounts of all the clients
+//! * `GET /clients/<id>` lists the accounts of a client, one per currency
+//! * `GET /transactions/<id>` shows a transaction along with its disputed and charged back amounts
+//!
+//! The requests are served concurrently by a fixed number of threads, but all of them are executed
+//! one by one by a single `Processor` in the order they arrive to its queue. Once the processor
+//! fails with a fatal error, it stops and the requests are answered with 503
+
+#[macro_use]
+extern crate derive_error;
+
+use serde::Serialize;
+use std::{
+    io::{self, Cursor, Read},
+    net::SocketAddr,
+    sync::{
+        mpsc::{sync_channel, SyncSender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+use tiny_http::{Header, Method, Request, Response};
+
+use engine::{
+    client::{Client, ClientId},
+    error::Error as EngineError,
+    format::{parse_json, InputFormat, Records},
+    input::Record,
+    processor::{Error as ProcessorError, Processor},
+    transaction::{Transaction, TransactionId},
+};
+use store::store::Store;
+
+/// The number of commands that may wait in the queue of the processor
+const QUEUE_SIZE: usize = 1024;
+/// The number of threads that read the requests and wait for their replies
+const WORKERS: usize = 8;
+/// The body of a request is cut at this size, a single record is much smaller
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+/// The code of a request that can't be parsed into a record
+const MALFORMED_CODE: &str = "input.malformed";
+/// The code of a request to an unknown path
+const NOT_FOUND_CODE: &str = "http.not_found";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The address can't be listened on
+    Bind(io::Error),
+}
+
+/// What a request asks the processor for
+enum Command {
+    Process(Record),
+    Clients,
+    Client(ClientId),
+    Transaction(TransactionId),
+}
+
+/// The status code and the JSON body of a response
+struct Reply {
+    status: u16,
+    body: String,
+}
+
+/// The body of the responses that don't carry any data
+#[derive(Serialize)]
+struct Outcome<'a> {
+    status: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+type Queue = SyncSender<(Command, SyncSender<Reply>)>;
+
+pub struct Server<CS, TS>
+where
+    CS: Store<ClientId, Client> + Send + 'static,
+    TS: Store<TransactionId, Transaction> + Send + 'static,
+{
+    http: Arc<tiny_http::Server>,
+    workers: Vec<JoinHandle<()>>,
+    processor: JoinHandle<Processor<CS, TS>>,
+}
+
+impl<CS, TS> Server<CS, TS>
+where
+    CS: Store<ClientId, Client> + Send + 'static,
+    TS: Store<TransactionId, Transaction> + Send + 'static,
+{
+    /// Listens on `address`, e.g. `127.0.0.1:0` to pick a free port
+    ///
+    /// # Errors
+    pub fn start(address: &str, processor: Processor<CS, TS>) -> Result<Self, Error> {
+        let http = Arc::new(tiny_http::Server::http(address).map_err(io::Error::other)?);
+
+        let (queue, receiver) = sync_channel::<(Command, SyncSender<Reply>)>(QUEUE_SIZE);
+        let processor = thread::spawn(move || {
+            let mut processor = processor;
+            for (command, reply) in receiver {
+                let (outcome, fatal) = execute(&mut processor, command);
+                // the client may have gone away, the command is executed anyway
+                reply.send(outcome).ok();
+                if fatal {
+                    // the commands still in the queue are dropped, hence answered with 503
+                    break;
+                }
+            }
+            processor
+        });
+
+        let workers = (0..WORKERS)
+            .map(|_| {
+                let http = Arc::clone(&http);
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    for request in http.incoming_requests() {
+                        serve(&queue, request);
+                    }
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            http,
+            workers,
+            processor,
+        })
+    }
+
+    /// # Panics
+    /// When listening on a Unix socket rather than on an IP address
+    #[must_use]
+    pub fn address(&self) -> SocketAddr {
+        self.http
+            .server_addr()
+            .to_ip()
+            .expect("Listening on an IP address")
+    }
+
+    /// Stops accepting the requests, waits for the queued ones and returns the processor
+    ///
+    /// # Panics
+    /// When the processor has panicked
+    #[must_use]
+    pub fn stop(self) -> Processor<CS, TS> {
+        // every unblock wakes up a single worker
+        for _ in &self.workers {
+            self.http.unblock();
+        }
+        for worker in self.workers {
+            worker.join().expect("Worker stopped");
+        }
+        self.processor.join().expect("Processor stopped")
+    }
+
+    /// Serves until the process is stopped
+    ///
+    /// # Panics
+    /// When serving the requests has panicked
+    pub fn join(self) {
+        for worker in self.workers {
+            worker.join().expect("Worker stopped");
+        }
+    }
+}
+
+fn serve(queue: &Queue, mut request: Request) {
+    let reply = match command(&mut request) {
+        Ok(command) => {
+            let (sender, receiver) = sync_channel(1);
+            queue
+                .send((command, sender))
+                .ok()
+                .and_then(|()| receiver.recv().ok())
+                .unwrap_or_else(|| status(503, "stopped", None, None))
+        }
+        Err(reply) => reply,
+    };
+    let response = Response::from_string(reply.body)
+        .with_status_code(reply.status)
+        .with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                .expect("Valid header"),
+        );
+    if let Err(error) = request.respond(response) {
+        log::warn!("Failed to respond: {error:?}");
+    }
+}
+
+/// Parses the request into a command, or replies right away when it can't
+fn command(request: &mut Request) -> Result<Command, Reply> {
+    let path = request
+        .url()
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('/')
+        .to_string();
+    let segments = path.split('/').skip(1).collect::<Vec<_>>();
+    match (request.method(), segments.as_slice()) {
+        (Method::Post, ["transactions"]) => record(request).map(Command::Process),
+        (Method::Get, ["clients"]) => Ok(Command::Clients),
+        (Method::Get, ["clients", id]) => id
+            .parse()
+            .map(Command::Client)
+            .map_err(|_| not_found(NOT_FOUND_CODE)),
+        (Method::Get, ["transactions", id]) => id
+            .parse()
+            .map(Command::Transaction)
+            .map_err(|_| not_found(NOT_FOUND_CODE)),
+        (_, ["transactions" | "clients", ..]) => {
+            Err(status(405, "failed", Some("http.method_not_allowed"), None))
+        }
+        _ => Err(not_found(NOT_FOUND_CODE)),
+    }
+}
+
+/// The body is either a JSON object, which may span several lines, or a CSV header row followed
+/// by a single row
+fn record(request: &mut Request) -> Result<Record, Reply> {
+    let csv = request.headers().iter().any(|header| {
+        header.field.equiv("Content-Type") && header.value.as_str().starts_with("text/csv")
+    });
+    let mut body = vec![];
+    request
+        .as_reader()
+        .take(MAX_BODY_SIZE)
+        .read_to_end(&mut body)
+        .map_err(|error| malformed(format!("{error:?}")))?;
+
+    if !csv {
+        return parse_json(&body).map_err(|error| malformed(format!("{error:?}")));
+    }
+    let mut records = Records::from_reader(Cursor::new(body), InputFormat::Csv)
+        .map_err(|error| malformed(format!("{error:?}")))?;
+    match (records.next(), records.next()) {
+        (Some(Ok(row)), None) => Ok(row.record),
+        (Some(Err(row)), _) => Err(malformed(format!("{:?}", row.error))),
+        _ => Err(malformed("Exactly one record is expected".to_string())),
+    }
+}
+
+/// The reply along with whether the processor has failed with a fatal error
+fn execute<CS, TS>(processor: &mut Processor<CS, TS>, command: Command) -> (Reply, bool)
+where
+    CS: Store<ClientId, Client>,
+    TS: Store<TransactionId, Transaction>,
+{
+    let result = match command {
+        Command::Process(record) => match processor.process(&record) {
+            Ok(()) => Ok(status(200, "accepted", None, None)),
+            Err(error) if error.is_fatal() => Err(error),
+            Err(error) => {
+                log::warn!("Failed to process record [{record:?}]: {error:?}");
+                Ok(status(422, "rejected", Some(error.code()), None))
+            }
+        },
+        Command::Clients => processor
+            .clients_csv()
+            .map(|clients| json(&clients.collect::<Vec<_>>())),
+        Command::Client(id) => processor.client_csv(id).map(|rows| {
+            if rows.is_empty() {
+                not_found(ProcessorError::ClientNotFound.code())
+            } else {
+                json(&rows)
+            }
+        }),
+        Command::Transaction(id) => processor.transaction(id).map(|transaction| {
+            transaction.map_or_else(
+                || not_found(ProcessorError::TransactionNotFound.code()),
+                |transaction| json(&transaction),
+            )
+        }),
+    };
+    result.map_or_else(
+        |error: EngineError| {
+            log::error!("Failed to execute the request: {error:?}");
+            (
+                status(500, "failed", Some(error.code()), None),
+                error.is_fatal(),
+            )
+        },
+        |reply| (reply, false),
+    )
+}
+
+fn json(data: &impl Serialize) -> Reply {
+    Reply {
+        status: 200,
+        body: serde_json::to_string(data).expect("Serializable"),
+    }
+}
+
+fn status(status: u16, name: &str, code: Option<&str>, error: Option<String>) -> Reply {
+    Reply {
+        status,
+        body: serde_json::to_string(&Outcome {
+            status: name,
+            code,
+            error,
+        })
+        .expect("Serializable"),
+    }
+}
+
+fn malformed(error: String) -> Reply {
+    status(400, "malformed", Some(MALFORMED_CODE), Some(error))
+}
+
+fn not_found(code: &str) -> Reply {
+    status(404, "not_found", Some(code), None)
+}
@@ -0,0 +1,89 @@
+//! HTTP interface to the [Simple Payment Engine](../engine/index.html),
+//! see the [server](../server/index.html) for the API
+
+use clap::Parser;
+
+use engine::{
+    currency::Currency,
+    processor::{NegativeBalances, Processor, WithdrawalDisputes},
+};
+use server::Server;
+use store::{store_db::StoreDBBuilder, store_mem::StoreMem};
+
+const TX_BUFFER_SIZE: usize = 1_000_000;
+
+#[derive(Parser)]
+#[clap(name = "Payment Engine Server")]
+#[clap(author = "Gregory Arefyev <gregory@recom.live>")]
+#[clap(version = "0.1.0")]
+pub struct Args {
+    #[clap(
+        short,
+        long,
+        default_value = "127.0.0.1:8080",
+        help = "Address to listen on"
+    )]
+    pub address: String,
+    #[clap(short, long, help = "Increase log level")]
+    pub verbose: bool,
+    #[clap(
+        long,
+        arg_enum,
+        default_value = "reject",
+        help = "Whether to reject the disputes of withdrawals or to reverse the withdrawals"
+    )]
+    pub withdrawal_disputes: WithdrawalDisputes,
+    #[clap(
+        long,
+        help = "Allow disputes to make the available funds negative, such accounts are flagged \
+                for collections in the output"
+    )]
+    pub negative_balances: bool,
+    #[clap(
+        long,
+        help = "Accept the administrative actions (unlock, close) of the support team"
+    )]
+    pub admin: bool,
+    #[clap(
+        long,
+        value_parser,
+        default_value = "USD",
+        help = "Currency of the records that don't have one"
+    )]
+    pub currency: Currency,
+}
+
+fn main() {
+    let args = Args::parse();
+    let log_level = if args.verbose {
+        tracing::Level::WARN
+    } else {
+        tracing::Level::ERROR
+    };
+
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(log_level)
+        .init();
+
+    let processor = Processor::new(
+        StoreMem::new(),
+        StoreDBBuilder::new(TX_BUFFER_SIZE)
+            .build()
+            .expect("StoreDB created"),
+    )
+    .set_withdrawal_disputes(args.withdrawal_disputes)
+    .set_negative_balances(if args.negative_balances {
+        NegativeBalances::Allow
+    } else {
+        NegativeBalances::Reject
+    })
+    .set_admin_input(args.admin)
+    .set_default_currency(args.currency)
+    .set_currency_column(true);
+
+    let server = Server::start(&args.address, processor).expect("Server started");
+    // printed regardless of the log level, e.g. for a client to learn the port chosen by the OS
+    eprintln!("Listening on {}", server.address());
+    server.join();
+}
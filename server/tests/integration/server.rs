@@ -0,0 +1,265 @@
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream},
+    thread,
+};
+
+use engine::{client::Client, processor::Processor, transaction::Transaction};
+use server::Server;
+use store::{error::Error, store::Store, store_mem::StoreMem};
+
+type TestServer = Server<StoreMem<u64, Client>, StoreMem<u64, Transaction>>;
+
+#[test]
+fn requests() {
+    let server = start();
+    let address = server.address();
+
+    for (request, expected) in [
+        (
+            post_json(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": 10.0}"#),
+            (200, r#"{"status":"accepted"}"#),
+        ),
+        (
+            post_csv("type,client,tx,amount,currency\ndeposit,1,2,5.0,EUR\n"),
+            (200, r#"{"status":"accepted"}"#),
+        ),
+        (
+            post_json(r#"{"type": "withdrawal", "client": 1, "tx": 3, "amount": 20.0}"#),
+            (
+                422,
+                r#"{"status":"rejected","code":"client.withdraw_insufficient_funds"}"#,
+            ),
+        ),
+        (
+            post_json(r#"{"type": "dispute", "client": 1, "tx": 1, "amount": "2.5"}"#),
+            (200, r#"{"status":"accepted"}"#),
+        ),
+        (
+            get("/clients/1"),
+            (
+                200,
+                r#"[{"client":1,"currency":"EUR","available":"5.0000","held":"0.0000","total":"5.0000","locked":false},{"client":1,"currency":"USD","available":"7.5000","held":"2.5000","total":"10.0000","locked":false}]"#,
+            ),
+        ),
+        (
+            get("/transactions/1"),
+            (
+                200,
+                r#"{"id":1,"client_id":1,"recipient_id":null,"currency":"USD","amount":"10.0000","action":"Deposit","disputed":"2.5000","slices":"2.5000","charged_back":"0.0000"}"#,
+            ),
+        ),
+        (
+            get("/transactions/3"),
+            (
+                404,
+                r#"{"status":"not_found","code":"processor.transaction_not_found"}"#,
+            ),
+        ),
+        (
+            get("/clients/2"),
+            (
+                404,
+                r#"{"status":"not_found","code":"processor.client_not_found"}"#,
+            ),
+        ),
+        (
+            get("/accounts"),
+            (404, r#"{"status":"not_found","code":"http.not_found"}"#),
+        ),
+        (
+            "DELETE /clients/1 HTTP/1.1\r\n\r\n".to_string(),
+            (
+                405,
+                r#"{"status":"failed","code":"http.method_not_allowed"}"#,
+            ),
+        ),
+    ] {
+        assert_eq!(
+            (expected.0, expected.1.to_string()),
+            send(address, &request),
+            "{request}"
+        );
+    }
+
+    let (status, body) = send(
+        address,
+        &post_json(
+            "{\n  \"type\": \"deposit\",\n  \"client\": 1,\n  \"tx\": 4,\n  \"amount\": 1.0\n}\n",
+        ),
+    );
+    assert_eq!(
+        (200, r#"{"status":"accepted"}"#.to_string()),
+        (status, body)
+    );
+
+    let (status, body) = send(address, &post_json("deposit,1,5,1.0"));
+    assert_eq!(400, status);
+    assert!(body.contains(r#""code":"input.malformed""#), "{body}");
+
+    let processor = server.stop();
+    assert_eq!(2, processor.clients_csv().expect("Clients read").count());
+}
+
+#[test]
+fn ordering() {
+    let server = start();
+    let address = server.address();
+
+    let senders = (0..8_u64)
+        .map(|sender| {
+            thread::spawn(move || {
+                for i in 0..10 {
+                    let tx = sender * 10 + i + 1;
+                    let (status, _) = send(
+                        address,
+                        &post_json(&format!(
+                            r#"{{"type": "deposit", "client": 1, "tx": {tx}, "amount": 1.0}}"#
+                        )),
+                    );
+                    assert_eq!(200, status, "Deposit {tx} accepted");
+                    // every client waits for the response, so the dispute is queued after
+                    // the deposit
+                    let (status, _) = send(
+                        address,
+                        &post_json(&format!(
+                            r#"{{"type": "dispute", "client": 1, "tx": {tx}}}"#
+                        )),
+                    );
+                    assert_eq!(200, status, "Dispute {tx} accepted");
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for sender in senders {
+        sender.join().expect("Sender finished");
+    }
+
+    let (status, body) = send(address, &get("/clients"));
+    assert_eq!(
+        (
+            200,
+            r#"[{"client":1,"currency":"USD","available":"0.0000","held":"80.0000","total":"80.0000","locked":false}]"#
+                .to_string()
+        ),
+        (status, body)
+    );
+
+    drop(server.stop());
+}
+
+#[test]
+fn stopped_on_fatal_error() {
+    let server = Server::start(
+        "127.0.0.1:0",
+        Processor::new(StoreMem::new(), FailingStore(StoreMem::new())),
+    )
+    .expect("Server started");
+    let address = server.address();
+
+    for (request, expected) in [
+        (
+            post_json(r#"{"type": "deposit", "client": 1, "tx": 1, "amount": 1.0}"#),
+            (200, r#"{"status":"accepted"}"#),
+        ),
+        (
+            post_json(r#"{"type": "deposit", "client": 1, "tx": 13, "amount": 1.0}"#),
+            (500, r#"{"status":"failed","code":"store.failure"}"#),
+        ),
+        (
+            post_json(r#"{"type": "deposit", "client": 1, "tx": 2, "amount": 1.0}"#),
+            (503, r#"{"status":"stopped"}"#),
+        ),
+        (get("/clients"), (503, r#"{"status":"stopped"}"#)),
+    ] {
+        assert_eq!(
+            (expected.0, expected.1.to_string()),
+            send(address, &request),
+            "{request}"
+        );
+    }
+
+    // the deposit queued after the failure isn't processed
+    let mut processor = server.stop();
+    assert!(processor.transaction(1).expect("Read").is_some());
+    assert!(processor.transaction(2).expect("Read").is_none());
+}
+
+/// Fails to store the transaction 13, which stops the processor
+struct FailingStore(StoreMem<u64, Transaction>);
+
+impl Store<u64, Transaction> for FailingStore {
+    fn insert(&mut self, key: u64, value: Transaction) -> Result<Option<Transaction>, Error> {
+        if key == 13 {
+            Err(sled::Error::Io(io::Error::other("Disk failed")).into())
+        } else {
+            self.0.insert(key, value)
+        }
+    }
+
+    fn remove(&mut self, key: &u64) -> Result<Option<Transaction>, Error> {
+        self.0.remove(key)
+    }
+
+    fn get(&mut self, key: &u64) -> Result<Option<&Transaction>, Error> {
+        self.0.get(key)
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = Result<u64, Error>> + '_> {
+        self.0.keys()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(u64, Transaction), Error>> + '_> {
+        self.0.iter()
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.0.flush()
+    }
+}
+
+fn start() -> TestServer {
+    Server::start(
+        "127.0.0.1:0",
+        Processor::new(StoreMem::new(), StoreMem::new()).set_currency_column(true),
+    )
+    .expect("Server started")
+}
+
+fn get(path: &str) -> String {
+    format!("GET {path} HTTP/1.1\r\n\r\n")
+}
+
+fn post_json(body: &str) -> String {
+    post("application/json", body)
+}
+
+fn post_csv(body: &str) -> String {
+    post("text/csv", body)
+}
+
+fn post(content_type: &str, body: &str) -> String {
+    format!(
+        "POST /transactions HTTP/1.1\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Sends a single request over a new connection and returns the status code and the body
+fn send(address: SocketAddr, request: &str) -> (u16, String) {
+    let request = request.replacen("\r\n", "\r\nConnection: close\r\n", 1);
+    let mut stream = TcpStream::connect(address).expect("Connected");
+    stream.write_all(request.as_bytes()).expect("Request sent");
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .expect("Response received");
+
+    let (head, body) = response.split_once("\r\n\r\n").expect("Headers ended");
+    let status = head
+        .split(' ')
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .expect("Status code");
+    (status, body.to_string())
+}
@@ -0,0 +1,12 @@
+//! The errors that any implementation of the `Store` may return
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The underlying database failed to read or write
+    Io(sled::Error),
+    /// A key or a value couldn't be serialized
+    Serialization(bincode::Error),
+    /// A key or a value read back from the database couldn't be deserialized
+    #[error(no_from)]
+    Corruption(bincode::Error),
+}
@@ -6,6 +6,11 @@
 
 //! Key-Value store engine with two interchangeable implementations
 
+#[macro_use]
+extern crate derive_error;
+
+pub mod error;
+mod lru;
 pub mod store;
 pub mod store_db;
 pub mod store_mem;
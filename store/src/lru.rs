@@ -0,0 +1,153 @@
+//! The map that keeps its entries in the order of recency, all the operations are O(1)
+use std::{collections::HashMap, hash::Hash};
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    /// The more recently used neighbour
+    prev: Option<usize>,
+    /// The less recently used neighbour
+    next: Option<usize>,
+}
+
+/// A doubly linked list of the entries laid out in a slab, plus an index from keys to the slots
+pub struct Lru<K, V> {
+    index: HashMap<K, usize>,
+    slots: Vec<Option<Node<K, V>>>,
+    free: Vec<usize>,
+    head: Option<usize>,
+    tail: Option<usize>,
+}
+
+impl<K: Clone + Eq + Hash, V> Default for Lru<K, V> {
+    fn default() -> Self {
+        Self {
+            index: HashMap::new(),
+            slots: vec![],
+            free: vec![],
+            head: None,
+            tail: None,
+        }
+    }
+}
+
+impl<K: Clone + Eq + Hash, V> Lru<K, V> {
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Marks the entry as the most recently used
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let slot = *self.index.get(key)?;
+        self.promote(slot);
+        Some(&self.node(slot).value)
+    }
+
+    /// Doesn't affect the order of recency
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        self.index.get(key).map(|slot| &self.node(*slot).value)
+    }
+
+    /// Inserts or replaces the entry and marks it as the most recently used
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(slot) = self.index.get(&key).copied() {
+            self.promote(slot);
+            Some(std::mem::replace(&mut self.node_mut(slot).value, value))
+        } else {
+            let node = Node {
+                key: key.clone(),
+                value,
+                prev: None,
+                next: None,
+            };
+            let slot = if let Some(slot) = self.free.pop() {
+                self.slots[slot] = Some(node);
+                slot
+            } else {
+                self.slots.push(Some(node));
+                self.slots.len() - 1
+            };
+            self.index.insert(key, slot);
+            self.push_front(slot);
+            None
+        }
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let slot = self.index.remove(key)?;
+        Some(self.take(slot).value)
+    }
+
+    /// Removes the least recently used entry
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let slot = self.tail?;
+        let node = self.take(slot);
+        self.index.remove(&node.key);
+        Some((node.key, node.value))
+    }
+
+    /// From the most to the least recently used
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        std::iter::successors(self.head, move |slot| self.node(*slot).next).map(move |slot| {
+            let node = self.node(slot);
+            (&node.key, &node.value)
+        })
+    }
+
+    fn node(&self, slot: usize) -> &Node<K, V> {
+        self.slots[slot].as_ref().expect("Slot is occupied")
+    }
+
+    fn node_mut(&mut self, slot: usize) -> &mut Node<K, V> {
+        self.slots[slot].as_mut().expect("Slot is occupied")
+    }
+
+    fn take(&mut self, slot: usize) -> Node<K, V> {
+        self.unlink(slot);
+        self.free.push(slot);
+        self.slots[slot].take().expect("Slot is occupied")
+    }
+
+    fn promote(&mut self, slot: usize) {
+        if self.head != Some(slot) {
+            self.unlink(slot);
+            self.push_front(slot);
+        }
+    }
+
+    fn push_front(&mut self, slot: usize) {
+        let head = self.head;
+        {
+            let node = self.node_mut(slot);
+            node.prev = None;
+            node.next = head;
+        }
+        if let Some(head) = head {
+            self.node_mut(head).prev = Some(slot);
+        } else {
+            self.tail = Some(slot);
+        }
+        self.head = Some(slot);
+    }
+
+    fn unlink(&mut self, slot: usize) {
+        let (prev, next) = {
+            let node = self.node(slot);
+            (node.prev, node.next)
+        };
+        if let Some(prev) = prev {
+            self.node_mut(prev).next = next;
+        } else {
+            self.head = next;
+        }
+        if let Some(next) = next {
+            self.node_mut(next).prev = prev;
+        } else {
+            self.tail = prev;
+        }
+    }
+}
@@ -1,16 +1,53 @@
 //! The common interface of a key-value store
-use std::error::Error;
+use super::error::Error;
 
 pub trait Store<K, V> {
     /// # Errors
-    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, Box<dyn Error>>;
+    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, Error>;
 
     /// # Errors
-    fn remove(&mut self, key: &K) -> Result<Option<V>, Box<dyn Error>>;
+    fn remove(&mut self, key: &K) -> Result<Option<V>, Error>;
 
     /// # Errors
-    fn get(&mut self, key: &K) -> Result<Option<&V>, Box<dyn Error>>;
+    fn get(&mut self, key: &K) -> Result<Option<&V>, Error>;
 
+    /// Lazily iterates over the keys in no particular order
+    fn keys(&self) -> Box<dyn Iterator<Item = Result<K, Error>> + '_>;
+
+    /// Lazily iterates over the pairs in no particular order, unlike `get` it doesn't affect
+    /// the in-memory part of a store (if any)
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(K, V), Error>> + '_>;
+
+    /// Makes sure all the modifications reach the storage, a no-op for in-memory stores
+    ///
     /// # Errors
-    fn keys(&self) -> Result<Vec<K>, Box<dyn Error>>;
+    fn flush(&mut self) -> Result<(), Error>;
+}
+
+/// A boxed store is a store too, e.g. `Box<dyn Store<K, V> + Send>` to choose the implementation
+/// at runtime
+impl<K, V, S: Store<K, V> + ?Sized> Store<K, V> for Box<S> {
+    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, Error> {
+        (**self).insert(key, value)
+    }
+
+    fn remove(&mut self, key: &K) -> Result<Option<V>, Error> {
+        (**self).remove(key)
+    }
+
+    fn get(&mut self, key: &K) -> Result<Option<&V>, Error> {
+        (**self).get(key)
+    }
+
+    fn keys(&self) -> Box<dyn Iterator<Item = Result<K, Error>> + '_> {
+        (**self).keys()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(K, V), Error>> + '_> {
+        (**self).iter()
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        (**self).flush()
+    }
 }
@@ -1,30 +1,53 @@
 //! The `StoreDB` hybrid key-value store is meant to provide control over the memory usage of a
 //! process at the cost of using HDD/SSD storage
-use bincode::{deserialize, serialize};
+use bincode::{deserialize, serialize, serialized_size};
 use serde::{Deserialize, Serialize};
 use sled::Db;
-use std::{
-    collections::{HashMap, VecDeque},
-    env::temp_dir,
-    error::Error,
-    fs::remove_dir_all,
-    hash::Hash,
-};
+use std::{collections::HashSet, env::temp_dir, fs::remove_dir_all, hash::Hash};
 
-use super::store::Store;
+use super::{error::Error, lru::Lru, store::Store};
 
 // below is the stable way to make an alias to a trait
 // very much looking forward to seeing https://github.com/rust-lang/rust/issues/41517 resolved =)
 pub trait Key: Clone + Eq + Hash + Serialize + for<'a> Deserialize<'a> {}
 impl<T> Key for T where T: Clone + Eq + Hash + Serialize + for<'a> Deserialize<'a> {}
 
-pub trait Value: Serialize + for<'a> Deserialize<'a> {}
-impl<T> Value for T where T: Serialize + for<'a> Deserialize<'a> {}
+pub trait Value: Clone + Serialize + for<'a> Deserialize<'a> {}
+impl<T> Value for T where T: Clone + Serialize + for<'a> Deserialize<'a> {}
+
+/// When the values kept in memory are written to the disk
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Durability {
+    /// Only when a value is evicted from memory, on `flush` and on drop (the fastest, but an
+    /// unclean exit loses up to `buffer_size` values)
+    WriteBack,
+    /// Every `insert` and `remove` is written and flushed to the disk before it returns
+    WriteThrough,
+    /// Same as `WriteBack` plus `flush` after every N calls to `insert` and `remove`
+    Periodic(usize),
+}
+
+/// The figures reported by `StoreDB::stats`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The serialized size of the keys and the values kept in memory
+    pub memory_bytes: u64,
+    pub memory_entries: usize,
+    /// The size of the database files
+    pub disk_bytes: u64,
+    /// The entries written to the database, including the ones also kept in memory. Under
+    /// write-back the values modified since they were last written are only counted once
+    /// they're evicted or flushed
+    pub disk_entries: usize,
+}
 
 /// The builder for `StoreDB`
 pub struct StoreDBBuilder {
     buffer_size: usize,
+    memory_limit: Option<u64>,
     db_path: Option<String>,
+    temp_dir: Option<String>,
+    durability: Durability,
 }
 
 impl StoreDBBuilder {
@@ -33,7 +56,21 @@ impl StoreDBBuilder {
     pub fn new(buffer_size: usize) -> Self {
         Self {
             buffer_size,
+            memory_limit: None,
             db_path: None,
+            temp_dir: None,
+            durability: Durability::WriteBack,
+        }
+    }
+
+    /// Optional limit of the in-memory part of the store in bytes, the least recently used values
+    /// are evicted to the disk once either the limit or `buffer_size` is exceeded.
+    /// The size of a value is approximated by the size of its serialized key and value
+    #[must_use]
+    pub fn set_memory_limit(self, bytes: u64) -> Self {
+        Self {
+            memory_limit: Some(bytes),
+            ..self
         }
     }
 
@@ -48,72 +85,170 @@ impl StoreDBBuilder {
         }
     }
 
+    /// Optional directory to create the temporary db directory in, the system temporary
+    /// directory by default. Makes no difference in persistent mode
+    #[must_use]
+    pub fn set_temp_dir(self, temp_dir: String) -> Self {
+        Self {
+            temp_dir: Some(temp_dir),
+            ..self
+        }
+    }
+
+    /// Optional durability policy, `Durability::WriteBack` by default
+    #[must_use]
+    pub fn set_durability(self, durability: Durability) -> Self {
+        Self { durability, ..self }
+    }
+
     /// # Errors
-    /// Fs-related errors may bubble up from `sled::open`
-    pub fn build<K: Key, V: Value>(&self) -> Result<StoreDB<K, V>, Box<dyn Error>> {
+    /// Fs-related errors may bubble up from `sled::open` as `Error::Io`
+    pub fn build<K: Key, V: Value>(&self) -> Result<StoreDB<K, V>, Error> {
         let (db_path, is_temporary) = if let Some(path) = &self.db_path {
             (path.clone(), false)
         } else {
             (
                 format!(
                     "{}/sled_db_{}.d",
-                    temp_dir().display(),
+                    self.temp_dir
+                        .clone()
+                        .unwrap_or_else(|| temp_dir().display().to_string()),
                     random_string::generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
                 ),
                 true,
             )
         };
         Ok(StoreDB {
-            memory: HashMap::new(),
-            buffer: VecDeque::new(),
+            memory: Lru::default(),
+            memory_bytes: 0,
+            dirty: HashSet::new(),
             buffer_size: self.buffer_size,
+            memory_limit: self.memory_limit,
             db_path: db_path.clone(),
             db_handle: sled::open(db_path)?,
             is_temporary,
+            durability: self.durability,
+            writes_since_flush: 0,
         })
     }
 }
 
+struct Entry<V> {
+    value: V,
+    bytes: u64,
+}
+
+/// The values in `memory` that aren't in `dirty` have an identical copy in the database
 pub struct StoreDB<K: Key, V: Value> {
-    memory: HashMap<K, V>,
-    buffer: VecDeque<K>,
+    memory: Lru<K, Entry<V>>,
+    memory_bytes: u64,
+    dirty: HashSet<K>,
     buffer_size: usize,
+    memory_limit: Option<u64>,
     db_path: String,
     db_handle: Db,
     is_temporary: bool,
+    durability: Durability,
+    writes_since_flush: usize,
 }
 
 impl<K: Key, V: Value> Drop for StoreDB<K, V> {
     fn drop(&mut self) {
-        if !self.is_temporary {
-            for (key, value) in &self.memory {
-                self.db_insert(key, value).expect("Stored");
-            }
-        }
-
-        self.db_handle.flush().unwrap();
-
         if self.is_temporary {
             remove_dir_all(&self.db_path).unwrap();
+        } else {
+            self.flush().expect("Stored");
         }
     }
 }
 
 impl<K: Key, V: Value> StoreDB<K, V> {
-    fn db_insert(&self, key: &K, value: &V) -> Result<(), Box<dyn Error>> {
-        self.db_handle.insert(
-            serialize(key).expect("Key serialized"),
-            serialize(value).expect("Value serialized"),
-        )?;
+    fn db_insert(&self, key: &K, value: &V) -> Result<(), Error> {
+        self.db_handle.insert(serialize(key)?, serialize(value)?)?;
+
+        Ok(())
+    }
+
+    fn db_get(&self, key: &K) -> Result<Option<V>, Error> {
+        self.db_handle
+            .get(serialize(key)?)?
+            .map(|value_bin| deserialize(&value_bin).map_err(Error::Corruption))
+            .transpose()
+    }
+
+    fn db_remove(&self, key: &K) -> Result<Option<V>, Error> {
+        self.db_handle
+            .remove(serialize(key)?)?
+            .map(|value_bin| deserialize(&value_bin).map_err(Error::Corruption))
+            .transpose()
+    }
+
+    fn memory_insert(&mut self, key: K, value: V) -> Result<Option<V>, Error> {
+        let bytes = serialized_size(&key)? + serialized_size(&value)?;
+        self.memory_bytes += bytes;
+        Ok(self.memory.insert(key, Entry { value, bytes }).map(|old| {
+            self.memory_bytes -= old.bytes;
+            old.value
+        }))
+    }
+
+    fn memory_remove(&mut self, key: &K) -> Option<V> {
+        self.memory.remove(key).map(|old| {
+            self.memory_bytes -= old.bytes;
+            old.value
+        })
+    }
+
+    fn is_memory_exceeded(&self) -> bool {
+        self.memory.len() > self.buffer_size
+            || self
+                .memory_limit
+                .is_some_and(|limit| self.memory_bytes > limit)
+    }
+
+    fn move_lru(&mut self) -> Result<(), Error> {
+        // the most recently used value is kept even if the limits are zero,
+        // so that `get` can return a reference to it
+        while self.memory.len() > 1 && self.is_memory_exceeded() {
+            let (key, entry) = self.memory.pop_lru().expect("Least recent value popped");
+            self.memory_bytes -= entry.bytes;
+            if self.dirty.remove(&key) {
+                self.db_insert(&key, &entry.value)?;
+            }
+        }
 
         Ok(())
     }
 
-    fn move_lru(&mut self) -> Result<(), Box<dyn Error>> {
-        if self.buffer.len() > self.buffer_size {
-            let key = self.buffer.pop_back().expect("Least recent key popped");
-            let value = self.memory.remove(&key).expect("Least recent value taken");
-            self.db_insert(&key, &value)?;
+    /// The keys kept in memory from the most to the least recently used, i.e. in the reverse
+    /// order of eviction
+    pub fn recency(&self) -> impl Iterator<Item = &K> + '_ {
+        self.memory.iter().map(|(key, _)| key)
+    }
+
+    /// Note that counting the entries on the disk takes a full scan of the database
+    ///
+    /// # Errors
+    pub fn stats(&self) -> Result<Stats, Error> {
+        Ok(Stats {
+            memory_bytes: self.memory_bytes,
+            memory_entries: self.memory.len(),
+            disk_bytes: self.db_handle.size_on_disk()?,
+            disk_entries: self.db_handle.len(),
+        })
+    }
+
+    /// Applies the durability policy after a modification of the store
+    fn written(&mut self) -> Result<(), Error> {
+        match self.durability {
+            Durability::WriteBack => {}
+            Durability::WriteThrough => self.flush()?,
+            Durability::Periodic(every) => {
+                self.writes_since_flush += 1;
+                if self.writes_since_flush >= every {
+                    self.flush()?;
+                }
+            }
         }
 
         Ok(())
@@ -121,66 +256,88 @@ impl<K: Key, V: Value> StoreDB<K, V> {
 }
 
 impl<K: Key, V: Value> Store<K, V> for StoreDB<K, V> {
-    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, Box<dyn Error>> {
-        if let Some(old_value) = self.memory.insert(key.clone(), value) {
-            Ok(Some(old_value))
+    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, Error> {
+        let old_value = if let Some(old_value) = self.memory_insert(key.clone(), value)? {
+            Some(old_value)
         } else {
-            let old_value = self
-                .db_handle
-                .remove(serialize(&key).expect("Key serialized"))?
-                .map(|old_value_bin| deserialize(&old_value_bin).expect("Old value deserialized"));
+            self.db_get(&key)?
+        };
 
-            self.buffer.push_front(key);
-            self.move_lru()?;
+        self.dirty.insert(key);
+        self.move_lru()?;
+        self.written()?;
 
-            Ok(old_value)
-        }
+        Ok(old_value)
     }
 
-    fn remove(&mut self, key: &K) -> Result<Option<V>, Box<dyn Error>> {
-        if let Some(old_value) = self.memory.remove(key) {
-            Ok(Some(old_value))
-        } else {
-            Ok(self
-                .db_handle
-                .remove(serialize(&key).expect("Key serialized"))?
-                .map(|old_value_bin| deserialize(&old_value_bin).expect("Old value deserialized")))
-        }
+    fn remove(&mut self, key: &K) -> Result<Option<V>, Error> {
+        self.dirty.remove(key);
+        let old_value = self.memory_remove(key);
+        let old_db_value = self.db_remove(key)?;
+        self.written()?;
+
+        Ok(old_value.or(old_db_value))
     }
 
     /// The implementation of `get` potentially mutates the instance of the `StoreDB` in order to
     /// maintain the MRU in-memory part of the data
-    fn get(&mut self, key: &K) -> Result<Option<&V>, Box<dyn Error>> {
+    fn get(&mut self, key: &K) -> Result<Option<&V>, Error> {
         if self.memory.contains_key(key) {
-            Ok(self.memory.get(key))
-        } else if let Some(value_bin) = self
-            .db_handle
-            .remove(serialize(key).expect("Key serialized"))?
-        {
-            self.memory.insert(
-                key.to_owned(),
-                deserialize(&value_bin).expect("Old value deserialized"),
-            );
-            self.buffer.push_front(key.to_owned());
+            Ok(self.memory.get(key).map(|entry| &entry.value))
+        } else if let Some(value) = self.db_get(key)? {
+            self.memory_insert(key.to_owned(), value)?;
             self.move_lru()?;
-            Ok(self.memory.get(key))
+            Ok(self.memory.peek(key).map(|entry| &entry.value))
         } else {
             Ok(None)
         }
     }
 
-    fn keys(&self) -> Result<Vec<K>, Box<dyn Error>> {
-        Ok(self
-            .memory
-            .keys()
-            .map::<Result<K, Box<dyn Error>>, _>(|k| Ok(k.clone()))
-            .chain(
+    fn keys(&self) -> Box<dyn Iterator<Item = Result<K, Error>> + '_> {
+        Box::new(
+            self.memory.iter().map(|(k, _)| Ok(k.clone())).chain(
                 self.db_handle
                     .iter()
                     .keys()
-                    .map(|k| Ok(deserialize(&k?).expect("Key deserialized"))),
-            )
-            .map(Result::unwrap)
-            .collect())
+                    .map(|k| deserialize(&k?).map_err(Error::Corruption))
+                    .filter(|k| !matches!(k, Ok(k) if self.memory.contains_key(k))),
+            ),
+        )
+    }
+
+    /// The values that are in memory are served from there (as they may be newer than the ones on
+    /// the disk), all the others are streamed from the database without being loaded into memory
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(K, V), Error>> + '_> {
+        Box::new(
+            self.memory
+                .iter()
+                .map(|(k, entry)| Ok((k.clone(), entry.value.clone())))
+                .chain(self.db_handle.iter().filter_map(|pair| {
+                    let pair = pair.map_err(Error::from).and_then(|(k, v)| {
+                        Ok((deserialize::<K>(&k).map_err(Error::Corruption)?, v))
+                    });
+                    match pair {
+                        Ok((k, _)) if self.memory.contains_key(&k) => None,
+                        Ok((k, v)) => {
+                            Some(deserialize(&v).map(|v| (k, v)).map_err(Error::Corruption))
+                        }
+                        Err(error) => Some(Err(error)),
+                    }
+                })),
+        )
+    }
+
+    /// Writes all the values that have been modified in memory to the disk, a value stays dirty
+    /// until it's written, so a failed flush may be retried
+    fn flush(&mut self) -> Result<(), Error> {
+        for key in self.dirty.iter().cloned().collect::<Vec<_>>() {
+            let entry = self.memory.peek(&key).expect("Dirty value is in memory");
+            self.db_insert(&key, &entry.value)?;
+            self.dirty.remove(&key);
+        }
+        self.db_handle.flush()?;
+        self.writes_since_flush = 0;
+
+        Ok(())
     }
 }
@@ -1,7 +1,7 @@
 //! The pure in-memory key-value store
-use std::{collections::HashMap, error::Error, hash::Hash};
+use std::{collections::HashMap, hash::Hash};
 
-use super::store::Store;
+use super::{error::Error, store::Store};
 
 #[derive(Default)]
 pub struct StoreMem<K: Eq + Clone + Hash, V> {
@@ -17,20 +17,28 @@ impl<K: Eq + Clone + Hash, V> StoreMem<K, V> {
     }
 }
 
-impl<K: Eq + Clone + Hash, V> Store<K, V> for StoreMem<K, V> {
-    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, Box<dyn Error>> {
+impl<K: Eq + Clone + Hash, V: Clone> Store<K, V> for StoreMem<K, V> {
+    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, Error> {
         Ok(self.memory.insert(key, value))
     }
 
-    fn remove(&mut self, key: &K) -> Result<Option<V>, Box<dyn Error>> {
+    fn remove(&mut self, key: &K) -> Result<Option<V>, Error> {
         Ok(self.memory.remove(key))
     }
 
-    fn get(&mut self, key: &K) -> Result<Option<&V>, Box<dyn Error>> {
+    fn get(&mut self, key: &K) -> Result<Option<&V>, Error> {
         Ok(self.memory.get(key))
     }
 
-    fn keys(&self) -> Result<Vec<K>, Box<dyn Error>> {
-        Ok(self.memory.keys().map(|k| (*k).clone()).collect())
+    fn keys(&self) -> Box<dyn Iterator<Item = Result<K, Error>> + '_> {
+        Box::new(self.memory.keys().map(|k| Ok((*k).clone())))
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = Result<(K, V), Error>> + '_> {
+        Box::new(self.memory.iter().map(|(k, v)| Ok((k.clone(), v.clone()))))
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
     }
 }
@@ -1,9 +1,10 @@
 use serde::{Deserialize, Serialize};
 
 mod store_db;
+mod store_db_durability;
 mod store_mem;
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 struct TestValue {
     id: usize,
 }
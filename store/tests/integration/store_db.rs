@@ -1,5 +1,8 @@
 use super::TestValue;
-use store::{store::Store, store_db::StoreDBBuilder};
+use store::{
+    store::Store,
+    store_db::{StoreDB, StoreDBBuilder},
+};
 
 #[test]
 fn cycle() {
@@ -10,7 +13,10 @@ fn cycle() {
         assert!(old_value.is_none(), "No previous value at {}", i);
     }
 
-    let mut keys = store.keys().expect("Keys read");
+    let mut keys = store
+        .keys()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Keys read");
     keys.sort();
     assert_eq!((1..=10).collect::<Vec<usize>>(), keys);
 
@@ -30,3 +36,93 @@ fn cycle() {
         assert_eq!(value.unwrap().id, i + 1, "Correct value at {}", i);
     }
 }
+
+#[test]
+fn eviction_order() {
+    let mut store = StoreDBBuilder::new(3).build().expect("Built");
+    let recency = |store: &StoreDB<usize, TestValue>| store.recency().copied().collect::<Vec<_>>();
+
+    for i in 1..=3 {
+        store.insert(i, TestValue::new(i)).expect("Inserted");
+    }
+    assert_eq!(vec![3, 2, 1], recency(&store));
+
+    store.get(&1).expect("Gotten");
+    store.insert(2, TestValue::new(2)).expect("Inserted");
+    assert_eq!(vec![2, 1, 3], recency(&store));
+
+    store.insert(4, TestValue::new(4)).expect("Inserted");
+    assert_eq!(vec![4, 2, 1], recency(&store), "3 is evicted");
+
+    store.remove(&2).expect("Removed");
+    assert_eq!(vec![4, 1], recency(&store));
+    for i in 5..=7 {
+        store.insert(i, TestValue::new(i)).expect("Inserted");
+    }
+    assert_eq!(vec![7, 6, 5], recency(&store), "No stale keys are evicted");
+
+    assert_eq!(store.get(&3).expect("Gotten").map(|v| v.id), Some(3));
+    assert_eq!(
+        vec![3, 7, 6],
+        recency(&store),
+        "3 is loaded back from the disk"
+    );
+    assert!(store.get(&2).expect("Gotten").is_none());
+}
+
+#[test]
+fn memory_limit() {
+    // a key and a value of `TestValue` take 16 bytes serialized
+    let mut store = StoreDBBuilder::new(100)
+        .set_memory_limit(50)
+        .build()
+        .expect("Built");
+
+    for i in 1..=10 {
+        store.insert(i, TestValue::new(i)).expect("Inserted");
+    }
+    let stats = store.stats().expect("Stats read");
+    assert_eq!(3, stats.memory_entries);
+    assert_eq!(48, stats.memory_bytes);
+    assert_eq!(
+        7, stats.disk_entries,
+        "The dirty values aren't on the disk yet"
+    );
+    assert_eq!(vec![10, 9, 8], store.recency().copied().collect::<Vec<_>>());
+
+    store.flush().expect("Flushed");
+    let stats = store.stats().expect("Stats read");
+    assert_eq!(10, stats.disk_entries);
+    assert!(stats.disk_bytes > 0);
+
+    store.remove(&10).expect("Removed");
+    let stats = store.stats().expect("Stats read");
+    assert_eq!(2, stats.memory_entries);
+    assert_eq!(32, stats.memory_bytes);
+    assert_eq!(9, stats.disk_entries);
+}
+
+#[test]
+fn iter_keeps_memory() {
+    let mut store = StoreDBBuilder::new(3).build().expect("Built");
+    for i in 1..=10 {
+        store.insert(i, TestValue::new(i)).expect("Inserted");
+    }
+    store.insert(1, TestValue::new(100)).expect("Inserted");
+
+    let mut pairs = store
+        .iter()
+        .map(|pair| pair.map(|(k, v)| (k, v.id)))
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Pairs read");
+    pairs.sort_unstable();
+    assert_eq!(
+        vec![(1, 100)]
+            .into_iter()
+            .chain((2..=10).map(|i| (i, i)))
+            .collect::<Vec<_>>(),
+        pairs,
+        "Every key once with the most recent value"
+    );
+    assert_eq!(vec![1, 10, 9], store.recency().copied().collect::<Vec<_>>());
+}
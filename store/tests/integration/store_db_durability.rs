@@ -0,0 +1,156 @@
+use random_string::generate;
+use serde::{ser::Error, Deserialize, Serialize, Serializer};
+use std::{
+    env::temp_dir,
+    fs::{copy, create_dir_all, read_dir, remove_dir_all},
+    mem::forget,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::TestValue;
+use store::{
+    store::Store,
+    store_db::{Durability, StoreDBBuilder},
+};
+
+#[test]
+fn write_through() {
+    // `forget` skips the drop of the store, which is as close to SIGKILL as it gets in a test,
+    // the files of the database are copied since the forgotten instance keeps them locked
+    let db_path = tmp_db_path();
+    let mut store = StoreDBBuilder::new(5)
+        .set_db_path(db_path.clone())
+        .set_durability(Durability::WriteThrough)
+        .build()
+        .expect("Built");
+    for i in 1..=3 {
+        store.insert(i, TestValue::new(i)).expect("Inserted");
+    }
+    store.remove(&2).expect("Removed");
+    forget(store);
+    let copy_path = tmp_db_path();
+    copy_dir(Path::new(&db_path), Path::new(&copy_path));
+
+    let mut store = StoreDBBuilder::new(5)
+        .set_db_path(copy_path.clone())
+        .build::<usize, TestValue>()
+        .expect("Reopened");
+    assert_eq!(store.get(&1).expect("Gotten").map(|v| v.id), Some(1));
+    assert!(store.get(&2).expect("Gotten").is_none());
+    assert_eq!(store.get(&3).expect("Gotten").map(|v| v.id), Some(3));
+    drop(store);
+
+    remove_dir_all(db_path).expect("Db removed");
+    remove_dir_all(copy_path).expect("Db copy removed");
+}
+
+#[test]
+fn periodic() {
+    let db_path = tmp_db_path();
+    let mut store = StoreDBBuilder::new(10)
+        .set_db_path(db_path.clone())
+        .set_durability(Durability::Periodic(2))
+        .build()
+        .expect("Built");
+    for i in 1..=5 {
+        store.insert(i, TestValue::new(i)).expect("Inserted");
+    }
+    forget(store);
+    let copy_path = tmp_db_path();
+    copy_dir(Path::new(&db_path), Path::new(&copy_path));
+
+    let mut store = StoreDBBuilder::new(10)
+        .set_db_path(copy_path.clone())
+        .build::<usize, TestValue>()
+        .expect("Reopened");
+    for i in 1..=4 {
+        assert_eq!(store.get(&i).expect("Gotten").map(|v| v.id), Some(i));
+    }
+    assert!(store.get(&5).expect("Gotten").is_none(), "Not flushed yet");
+    store.insert(6, TestValue::new(6)).expect("Inserted");
+    store.flush().expect("Flushed");
+    forget(store);
+    let second_copy_path = tmp_db_path();
+    copy_dir(Path::new(&copy_path), Path::new(&second_copy_path));
+
+    let mut store = StoreDBBuilder::new(10)
+        .set_db_path(second_copy_path.clone())
+        .build::<usize, TestValue>()
+        .expect("Reopened");
+    assert_eq!(store.get(&6).expect("Gotten").map(|v| v.id), Some(6));
+    drop(store);
+
+    for path in [db_path, copy_path, second_copy_path] {
+        remove_dir_all(path).expect("Db removed");
+    }
+}
+
+/// Fails to serialize the value 2 while `BROKEN` is set
+#[derive(Clone, Deserialize)]
+struct Fragile(usize);
+
+static BROKEN: AtomicBool = AtomicBool::new(false);
+
+impl Serialize for Fragile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0 == 2 && BROKEN.load(Ordering::SeqCst) {
+            Err(S::Error::custom("Broken"))
+        } else {
+            serializer.serialize_newtype_struct("Fragile", &self.0)
+        }
+    }
+}
+
+#[test]
+fn flush_retried() {
+    let db_path = tmp_db_path();
+    let mut store = StoreDBBuilder::new(5)
+        .set_db_path(db_path.clone())
+        .build()
+        .expect("Built");
+    for i in 1..=3 {
+        store.insert(i, Fragile(i)).expect("Inserted");
+    }
+    BROKEN.store(true, Ordering::SeqCst);
+    assert!(store.flush().is_err());
+    // the values that haven't been written by the failed flush are still dirty
+    BROKEN.store(false, Ordering::SeqCst);
+    store.flush().expect("Flushed");
+    forget(store);
+    let copy_path = tmp_db_path();
+    copy_dir(Path::new(&db_path), Path::new(&copy_path));
+
+    let mut store = StoreDBBuilder::new(5)
+        .set_db_path(copy_path.clone())
+        .build::<usize, Fragile>()
+        .expect("Reopened");
+    for i in 1..=3 {
+        assert_eq!(store.get(&i).expect("Gotten").map(|v| v.0), Some(i));
+    }
+    drop(store);
+
+    remove_dir_all(db_path).expect("Db removed");
+    remove_dir_all(copy_path).expect("Db copy removed");
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    create_dir_all(to).expect("Directory created");
+    for entry in read_dir(from).expect("Directory read") {
+        let path = entry.expect("Entry read").path();
+        let target = to.join(path.file_name().expect("File name"));
+        if path.is_dir() {
+            copy_dir(&path, &target);
+        } else {
+            copy(&path, &target).expect("File copied");
+        }
+    }
+}
+
+fn tmp_db_path() -> String {
+    format!(
+        "{}/sled_db_test_{}.d",
+        temp_dir().display(),
+        generate(16, "abcdefghijklmnopqrstuvwxyz1234567890")
+    )
+}
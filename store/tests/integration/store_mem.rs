@@ -10,7 +10,10 @@ fn cycle() {
         assert!(old_value.is_none(), "No previous value at {}", i);
     }
 
-    let mut keys = store.keys().expect("Keys read");
+    let mut keys = store
+        .keys()
+        .collect::<Result<Vec<_>, _>>()
+        .expect("Keys read");
     keys.sort();
     assert_eq!((1..=10).collect::<Vec<usize>>(), keys);
 
@@ -30,3 +33,12 @@ fn cycle() {
         assert_eq!(value.unwrap().id, i + 1, "Correct value at {}", i);
     }
 }
+
+#[test]
+fn boxed() {
+    let mut store: Box<dyn Store<usize, TestValue>> = Box::new(StoreMem::new());
+    assert!(store.insert(1, TestValue::new(1)).expect("Inserted").is_none());
+    assert_eq!(store.get(&1).expect("Gotten").expect("Found").id, 1);
+    assert_eq!(store.iter().count(), 1);
+    assert_eq!(store.remove(&1).expect("Removed").expect("Found").id, 1);
+}